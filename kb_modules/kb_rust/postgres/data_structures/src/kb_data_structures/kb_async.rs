@@ -0,0 +1,407 @@
+
+use pq_sys::*;
+use std::ffi::{CStr, CString};
+use std::future::Future;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+
+use super::kb_sql_state::{classify_sqlstate, KbError};
+use super::postgres_setup::create_pg_connection;
+
+/// Carries a raw libpq connection handle across `.await` points so the
+/// futures in this module can be moved onto another thread's task — e.g.
+/// via `tokio::spawn` on a multi-threaded runtime — despite `*mut PGconn`
+/// itself not being `Send`.
+///
+/// Safety: libpq forbids concurrent use of one `PGconn` from multiple
+/// threads at once, but permits handing a connection off between threads as
+/// long as only one thread touches it at a time. Every function in this
+/// module takes an `AsyncConn` by value and drives it to completion
+/// sequentially, so this invariant holds as long as callers don't share one
+/// `AsyncConn` across concurrently-polled futures.
+#[derive(Clone, Copy)]
+pub struct AsyncConn(pub *mut PGconn);
+
+unsafe impl Send for AsyncConn {}
+
+/// Opens a connection for use with this module's async functions: the same
+/// setup as `create_pg_connection`, but immediately switched into libpq's
+/// non-blocking mode via `PQsetnonblocking`. Without this, `PQsendQuery`/
+/// `PQsendQueryParams` can still block the calling thread while libpq
+/// flushes the request, despite `PqResultFuture`'s poll-and-park loop —
+/// non-blocking mode is what makes that loop actually non-blocking instead
+/// of just shaped like it.
+pub fn create_async_pg_connection(
+    dbname: Option<&str>,
+    user: Option<&str>,
+    password: Option<&str>,
+    host: Option<&str>,
+    port: Option<&str>,
+) -> Result<AsyncConn, KbError> {
+    let conn = create_pg_connection(dbname, user, password, host, port);
+    if conn.is_null() {
+        return Err(KbError::Permanent("Failed to connect".to_string()));
+    }
+    if unsafe { PQsetnonblocking(conn, 1) } != 0 {
+        let msg = unsafe { CStr::from_ptr(PQerrorMessage(conn)) }.to_string_lossy().to_string();
+        unsafe { PQfinish(conn) };
+        return Err(KbError::Permanent(msg));
+    }
+    Ok(AsyncConn(conn))
+}
+
+/// Thin `AsRawFd` wrapper so libpq's bare `PQsocket` fd can be registered
+/// with tokio's reactor via `AsyncFd`.
+struct ConnFd(RawFd);
+
+impl AsRawFd for ConnFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// Drives one already-sent non-blocking request (`PQsendQuery`/
+/// `PQsendQueryParams` already issued on `conn`) to completion by polling
+/// `PQconsumeInput`/`PQisBusy` and parking on the connection's socket
+/// instead of blocking the OS thread, so a single task can service
+/// thousands of concurrent in-flight writes.
+struct PqResultFuture {
+    conn: AsyncConn,
+    async_fd: AsyncFd<ConnFd>,
+}
+
+impl Future for PqResultFuture {
+    type Output = Result<*mut PGresult, KbError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            if unsafe { PQconsumeInput(this.conn.0) } == 0 {
+                let msg = unsafe { CStr::from_ptr(PQerrorMessage(this.conn.0)) }
+                    .to_string_lossy()
+                    .to_string();
+                return Poll::Ready(Err(KbError::Permanent(msg)));
+            }
+
+            if unsafe { PQisBusy(this.conn.0) } == 1 {
+                let mut guard = match this.async_fd.poll_read_ready(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(KbError::Permanent(e.to_string()))),
+                    Poll::Pending => return Poll::Pending,
+                };
+                guard.clear_ready();
+                continue;
+            }
+
+            break;
+        }
+
+        // PQisBusy == 0 guarantees the rest of the response is already
+        // buffered, so draining to the NULL sentinel here cannot block.
+        let res = unsafe { PQgetResult(this.conn.0) };
+        loop {
+            let extra = unsafe { PQgetResult(this.conn.0) };
+            if extra.is_null() {
+                break;
+            }
+            unsafe { PQclear(extra) };
+        }
+        Poll::Ready(Ok(res))
+    }
+}
+
+fn socket_future(conn: AsyncConn) -> Result<PqResultFuture, KbError> {
+    let fd = unsafe { PQsocket(conn.0) };
+    let async_fd = AsyncFd::new(ConnFd(fd)).map_err(|e| KbError::Permanent(e.to_string()))?;
+    Ok(PqResultFuture { conn, async_fd })
+}
+
+async fn exec_async(conn: AsyncConn, sql: &str) -> Result<*mut PGresult, KbError> {
+    let sql_c = CString::new(sql).unwrap();
+    if unsafe { PQsendQuery(conn.0, sql_c.as_ptr()) } != 1 {
+        let msg = unsafe { CStr::from_ptr(PQerrorMessage(conn.0)) }.to_string_lossy().to_string();
+        return Err(KbError::Permanent(msg));
+    }
+    socket_future(conn)?.await
+}
+
+async fn exec_params_async(
+    conn: AsyncConn,
+    sql: &str,
+    param_values: &[*const i8],
+) -> Result<*mut PGresult, KbError> {
+    let sql_c = CString::new(sql).unwrap();
+    let sent = unsafe {
+        PQsendQueryParams(
+            conn.0,
+            sql_c.as_ptr(),
+            param_values.len() as i32,
+            ptr::null(),
+            param_values.as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            0,
+        )
+    };
+    if sent != 1 {
+        let msg = unsafe { CStr::from_ptr(PQerrorMessage(conn.0)) }.to_string_lossy().to_string();
+        return Err(KbError::Permanent(msg));
+    }
+    socket_future(conn)?.await
+}
+
+async fn rollback_async(conn: AsyncConn) {
+    if let Ok(res) = exec_async(conn, "ROLLBACK").await {
+        unsafe { PQclear(res) };
+    }
+}
+
+/// Async counterpart to `retry_or_give_up` that backs off with a
+/// non-blocking timer (`tokio::time::sleep`) instead of `thread::sleep`, so
+/// a retrying write never ties up an OS thread while it waits.
+async fn retry_or_give_up_async(err: KbError, attempt: i32, max_retries: i32, retry_delay: f64) -> Option<KbError> {
+    match err {
+        KbError::NoData => Some(KbError::NoData),
+        KbError::Permanent(_) => Some(err),
+        KbError::Transient(_) if attempt < max_retries => {
+            tokio::time::sleep(Duration::from_secs_f64(retry_delay)).await;
+            None
+        }
+        KbError::Transient(_) => Some(err),
+    }
+}
+
+/// Non-blocking counterpart to `push_stream_data`: the same
+/// BEGIN → count → lock-oldest → update → COMMIT state machine, but driven
+/// through libpq's non-blocking API so the retry loop never blocks the
+/// calling thread. Takes `conn` as an `AsyncConn` (not a bare `*mut PGconn`)
+/// so the returned future is `Send` and can be `tokio::spawn`'d onto a
+/// multi-threaded runtime.
+pub async fn push_stream_data_async(
+    conn: AsyncConn,
+    base_table: &str,
+    path: &str,
+    data: &str,
+    max_retries: i32,
+    retry_delay: f64,
+) -> Result<(), KbError> {
+    if path.is_empty() {
+        return Err(KbError::Permanent("Path cannot be empty or None".to_string()));
+    }
+
+    let path_cstr = CString::new(path).unwrap();
+    let param_values: [*const i8; 1] = [path_cstr.as_ptr()];
+
+    for attempt in 1..=max_retries {
+        match exec_async(conn, "BEGIN").await {
+            Ok(res) => unsafe { PQclear(res) },
+            Err(err) => return Err(err),
+        }
+
+        // 1) ensure there's at least one record to update
+        let query_buf = format!("SELECT COUNT(*) as count FROM {} WHERE path = $1", base_table);
+        let res = match exec_params_async(conn, &query_buf, &param_values).await {
+            Ok(res) => res,
+            Err(err) => {
+                rollback_async(conn).await;
+                match retry_or_give_up_async(err, attempt, max_retries, retry_delay).await {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
+        };
+        if unsafe { PQresultStatus(res) } != PGRES_TUPLES_OK {
+            let classified = classify_sqlstate(res);
+            unsafe { PQclear(res) };
+            rollback_async(conn).await;
+            match retry_or_give_up_async(classified, attempt, max_retries, retry_delay).await {
+                Some(err) => return Err(err),
+                None => continue,
+            }
+        }
+        let total: i32 = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_str().unwrap().parse().unwrap() };
+        unsafe { PQclear(res) };
+        if total == 0 {
+            rollback_async(conn).await;
+            return Err(KbError::NoData);
+        }
+
+        // 2) try to lock the oldest record regardless of valid status (true circular buffer)
+        let query_buf = format!(
+            "SELECT id FROM {} WHERE path = $1 ORDER BY recorded_at ASC FOR UPDATE SKIP LOCKED LIMIT 1",
+            base_table
+        );
+        let res = match exec_params_async(conn, &query_buf, &param_values).await {
+            Ok(res) => res,
+            Err(err) => {
+                rollback_async(conn).await;
+                match retry_or_give_up_async(err, attempt, max_retries, retry_delay).await {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
+        };
+        if unsafe { PQresultStatus(res) } != PGRES_TUPLES_OK {
+            let classified = classify_sqlstate(res);
+            unsafe { PQclear(res) };
+            rollback_async(conn).await;
+            match retry_or_give_up_async(classified, attempt, max_retries, retry_delay).await {
+                Some(err) => return Err(err),
+                None => continue,
+            }
+        }
+        if unsafe { PQntuples(res) } == 0 {
+            unsafe { PQclear(res) };
+            rollback_async(conn).await;
+            let msg = format!("Could not lock any row for path='{}'", path);
+            match retry_or_give_up_async(KbError::Transient(msg), attempt, max_retries, retry_delay).await {
+                Some(err) => return Err(err),
+                None => continue,
+            }
+        }
+
+        let record_id = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_str().unwrap().to_string() };
+        unsafe { PQclear(res) };
+
+        // 3) perform the update with valid=TRUE (always overwrites oldest record)
+        let query_buf = format!(
+            "UPDATE {} SET data = $1, recorded_at = NOW(), valid = TRUE WHERE id = $2 RETURNING id",
+            base_table
+        );
+        let data_cstr = CString::new(data).unwrap();
+        let record_id_cstr = CString::new(record_id).unwrap();
+        let update_params: [*const i8; 2] = [data_cstr.as_ptr(), record_id_cstr.as_ptr()];
+        let res = match exec_params_async(conn, &query_buf, &update_params).await {
+            Ok(res) => res,
+            Err(err) => {
+                rollback_async(conn).await;
+                match retry_or_give_up_async(err, attempt, max_retries, retry_delay).await {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
+        };
+        let status = unsafe { PQresultStatus(res) };
+        if status != PGRES_TUPLES_OK || unsafe { PQntuples(res) } != 1 {
+            let classified = if status != PGRES_TUPLES_OK {
+                classify_sqlstate(res)
+            } else {
+                KbError::Permanent("Failed to update record".to_string())
+            };
+            unsafe { PQclear(res) };
+            rollback_async(conn).await;
+            match retry_or_give_up_async(classified, attempt, max_retries, retry_delay).await {
+                Some(err) => return Err(err),
+                None => continue,
+            }
+        }
+        unsafe { PQclear(res) };
+
+        match exec_async(conn, "COMMIT").await {
+            Ok(res) => unsafe { PQclear(res) },
+            Err(err) => {
+                rollback_async(conn).await;
+                match retry_or_give_up_async(err, attempt, max_retries, retry_delay).await {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    Err(KbError::Permanent("Unexpected error in push_stream_data_async".to_string()))
+}
+
+/// Non-blocking counterpart to `set_status_data`: the same upsert-and-retry
+/// state machine, driven through libpq's non-blocking API. Takes `conn` as
+/// an `AsyncConn` for the same reason `push_stream_data_async` does — so
+/// the returned future is `Send` and can be `tokio::spawn`'d onto a
+/// multi-threaded runtime.
+pub async fn set_status_data_async(
+    conn: AsyncConn,
+    base_table: &str,
+    path: &str,
+    data: &str,
+    retry_count: i32,
+    retry_delay: f64,
+) -> Result<String, KbError> {
+    if path.is_empty() {
+        return Err(KbError::Permanent("Path cannot be empty or NULL".to_string()));
+    }
+    if data.is_empty() {
+        return Err(KbError::Permanent("Data cannot be empty or NULL".to_string()));
+    }
+    if retry_count < 0 {
+        return Err(KbError::Permanent("Retry count must be non-negative".to_string()));
+    }
+    if retry_delay < 0.0 {
+        return Err(KbError::Permanent("Retry delay must be non-negative".to_string()));
+    }
+
+    let query_buf = format!(
+        "INSERT INTO {} (path, data) VALUES ($1, $2) ON CONFLICT (path) DO UPDATE SET data = EXCLUDED.data RETURNING path, (xmax = 0) AS was_inserted",
+        base_table
+    );
+    let path_cstr = CString::new(path).unwrap();
+    let data_cstr = CString::new(data).unwrap();
+    let param_values: [*const i8; 2] = [path_cstr.as_ptr(), data_cstr.as_ptr()];
+
+    for attempt in 0..=retry_count {
+        match exec_async(conn, "BEGIN").await {
+            Ok(res) => unsafe { PQclear(res) },
+            Err(err) => return Err(err),
+        }
+
+        let res = match exec_params_async(conn, &query_buf, &param_values).await {
+            Ok(res) => res,
+            Err(classified) => {
+                rollback_async(conn).await;
+                match retry_or_give_up_async(classified, attempt + 1, retry_count + 1, retry_delay).await {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
+        };
+        let status = unsafe { PQresultStatus(res) };
+        if status != PGRES_TUPLES_OK {
+            let classified = classify_sqlstate(res);
+            unsafe { PQclear(res) };
+            rollback_async(conn).await;
+            match retry_or_give_up_async(classified, attempt + 1, retry_count + 1, retry_delay).await {
+                Some(err) => return Err(err),
+                None => continue,
+            }
+        }
+
+        if unsafe { PQntuples(res) } == 0 {
+            unsafe { PQclear(res) };
+            rollback_async(conn).await;
+            return Err(KbError::Permanent("Database operation completed but no result was returned".to_string()));
+        }
+
+        let returned_path = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_str().unwrap().to_string() };
+        let was_inserted = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 1)).to_str().unwrap() == "t" };
+        let operation = if was_inserted { "inserted" } else { "updated" };
+        unsafe { PQclear(res) };
+
+        match exec_async(conn, "COMMIT").await {
+            Ok(res) => unsafe { PQclear(res) },
+            Err(err) => return Err(err),
+        }
+
+        return Ok(format!("Successfully {} data for path: {}", operation, returned_path));
+    }
+
+    Err(KbError::Permanent(format!(
+        "Failed to set status data for path '{}' after {} attempts",
+        path,
+        retry_count + 1
+    )))
+}
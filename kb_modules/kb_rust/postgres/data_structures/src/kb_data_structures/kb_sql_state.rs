@@ -0,0 +1,76 @@
+
+use pq_sys::*;
+use std::ffi::CStr;
+use std::thread;
+use std::time::Duration;
+
+/// Distinguishes failures a caller can retry from ones it can't, replacing
+/// the old convention of matching substrings (e.g. `"No records found"`) in
+/// a libpq error message, which is fragile and locale-dependent.
+#[derive(Debug, Clone)]
+pub enum KbError {
+    /// A transient condition (serialization failure, deadlock, lock
+    /// timeout) — safe to retry with backoff.
+    Transient(String),
+    /// A permanent condition (constraint violation, syntax/access error, or
+    /// a max-retries exhaustion) — retrying would not help.
+    Permanent(String),
+    /// The query legitimately found nothing (SQLSTATE `02000`), or the
+    /// caller's own pre-check found no rows to operate on.
+    NoData,
+}
+
+/// Reads the 5-character SQLSTATE off a failed `res`, or `None` if the
+/// server didn't attach one (e.g. a connection-level failure).
+pub fn sqlstate_code(res: *mut PGresult) -> Option<String> {
+    unsafe {
+        let field = PQresultErrorField(res, PG_DIAG_SQLSTATE as i32);
+        if field.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(field).to_string_lossy().to_string())
+        }
+    }
+}
+
+/// Reads the 5-character SQLSTATE off a failed `res` via
+/// `PQresultErrorField(res, PG_DIAG_SQLSTATE)` and classifies it by class
+/// (the first two characters) instead of matching on the error message text.
+/// Class `40` (serialization_failure, deadlock_detected) and `55`
+/// (lock_not_available) are transient; `23` (integrity constraint
+/// violations) and `42` (syntax/access) are permanent; `02000` is `NoData`.
+pub fn classify_sqlstate(res: *mut PGresult) -> KbError {
+    let message = unsafe { CStr::from_ptr(PQresultErrorMessage(res)) }
+        .to_string_lossy()
+        .to_string();
+
+    let Some(code) = sqlstate_code(res) else {
+        return KbError::Permanent(message);
+    };
+
+    if code == "02000" {
+        return KbError::NoData;
+    }
+
+    match code.get(0..2) {
+        Some("40") | Some("55") => KbError::Transient(message),
+        Some("23") | Some("42") => KbError::Permanent(message),
+        _ => KbError::Permanent(message),
+    }
+}
+
+/// Given a classified failure partway through a retry loop, decides whether
+/// to back off and retry (`None`) or give up (`Some(err)`). `NoData` and
+/// `Permanent` never retry; `Transient` retries until `attempt` reaches
+/// `max_retries`, then surfaces as a failure too.
+pub fn retry_or_give_up(err: KbError, attempt: i32, max_retries: i32, retry_delay: f64) -> Option<KbError> {
+    match err {
+        KbError::NoData => Some(KbError::NoData),
+        KbError::Permanent(_) => Some(err),
+        KbError::Transient(_) if attempt < max_retries => {
+            thread::sleep(Duration::from_secs_f64(retry_delay));
+            None
+        }
+        KbError::Transient(_) => Some(err),
+    }
+}
@@ -0,0 +1,201 @@
+
+use pq_sys::*;
+use std::ffi::CStr;
+use std::os::raw::c_void;
+use std::thread;
+use std::time::Duration;
+
+use super::kb_sql_state::{classify_sqlstate, KbError};
+
+/// A decoded `pg_notify` event delivered by `subscribe_status`.
+#[derive(Debug, Clone)]
+pub struct StatusNotification {
+    pub path: String,
+    pub operation: String,
+    pub payload: String,
+}
+
+/// This crate has no JSON dependency — everything here is hand-rolled libpq
+/// FFI — so payloads are built and parsed with plain string escaping rather
+/// than a serializer. Escapes every character the JSON spec requires
+/// escaping (`"`, `\`, and all of U+0000–U+001F), not just the three most
+/// likely to show up in practice — a `path` containing a bare tab or
+/// carriage return would otherwise produce invalid JSON on the
+/// `pg_notify` channel. `extract_json_field` below reverses each of these.
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn sql_escape(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+/// Extracts `"field":"value"` from a flat, single-level JSON object built
+/// by [`notify_statement`]. Not a general JSON parser — only handles the
+/// shape this module itself produces.
+fn extract_json_field(payload: &str, field: &str) -> Option<String> {
+    let needle = format!("\"{}\":\"", field);
+    let start = payload.find(&needle)? + needle.len();
+    let rest = &payload[start..];
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('b') => value.push('\u{08}'),
+                Some('f') => value.push('\u{0C}'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    if let Some(codepoint) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        value.push(codepoint);
+                    }
+                }
+                Some(other) => value.push(other),
+                None => {}
+            },
+            '"' => return Some(value),
+            other => value.push(other),
+        }
+    }
+    None
+}
+
+fn decode_notification(payload: &str) -> Option<StatusNotification> {
+    let path = extract_json_field(payload, "path")?;
+    let operation = extract_json_field(payload, "operation")?;
+    Some(StatusNotification {
+        path,
+        operation,
+        payload: payload.to_string(),
+    })
+}
+
+/// The channel a given stream/status table's change feed is published on.
+fn channel_name(base_table: &str) -> String {
+    format!("{}_status", base_table)
+}
+
+/// Builds the `pg_notify(channel, payload)` statement `set_status_data`
+/// runs inside its upsert transaction, so subscribers watching
+/// `<base_table>_status` learn about the write without polling.
+pub fn notify_statement(base_table: &str, path: &str, operation: &str) -> String {
+    let payload = format!(
+        "{{\"path\":\"{}\",\"operation\":\"{}\"}}",
+        json_escape(path),
+        json_escape(operation)
+    );
+    format!(
+        "SELECT pg_notify('{}', '{}')",
+        channel_name(base_table),
+        sql_escape(&payload)
+    )
+}
+
+/// Issues `LISTEN` on `<base_table>_status` and drives `PQconsumeInput` /
+/// `PQnotifies` on `conn`'s socket, dispatching a decoded
+/// [`StatusNotification`] to `callback` for every matching notification.
+/// `paths` filters which paths are dispatched; an empty slice dispatches
+/// everything on the channel. `callback` returns `false` to stop
+/// subscribing. This turns the status table into a live change feed
+/// instead of a key callers have to busy-poll.
+pub fn subscribe_status<F>(
+    conn: *mut PGconn,
+    base_table: &str,
+    paths: &[String],
+    mut callback: F,
+) -> Result<(), KbError>
+where
+    F: FnMut(StatusNotification) -> bool,
+{
+    let channel = channel_name(base_table);
+    let listen_sql = format!("LISTEN \"{}\"", channel);
+    let listen_c = std::ffi::CString::new(listen_sql).unwrap();
+    let res = unsafe { PQexec(conn, listen_c.as_ptr()) };
+    if unsafe { PQresultStatus(res) } != PGRES_COMMAND_OK {
+        let classified = classify_sqlstate(res);
+        unsafe { PQclear(res) };
+        return Err(classified);
+    }
+    unsafe { PQclear(res) };
+
+    loop {
+        if unsafe { PQconsumeInput(conn) } == 0 {
+            let msg = unsafe { CStr::from_ptr(PQerrorMessage(conn)) }.to_string_lossy().to_string();
+            return Err(KbError::Permanent(msg));
+        }
+
+        loop {
+            let notify = unsafe { PQnotifies(conn) };
+            if notify.is_null() {
+                break;
+            }
+            let relname = unsafe { CStr::from_ptr((*notify).relname) }.to_string_lossy().to_string();
+            let extra = unsafe { CStr::from_ptr((*notify).extra) }.to_string_lossy().to_string();
+            unsafe { PQfreemem(notify as *mut c_void) };
+
+            if relname != channel {
+                continue;
+            }
+            let Some(decoded) = decode_notification(&extra) else {
+                continue;
+            };
+            if !paths.is_empty() && !paths.iter().any(|p| p == &decoded.path) {
+                continue;
+            }
+            if !callback(decoded) {
+                return Ok(());
+            }
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_escape_covers_all_json_mandated_control_characters() {
+        assert_eq!(json_escape("a\"b\\c\nd\re\tf"), "a\\\"b\\\\c\\nd\\re\\tf");
+        assert_eq!(json_escape("\u{08}\u{0C}"), "\\b\\f");
+        assert_eq!(json_escape("\u{01}"), "\\u0001");
+    }
+
+    #[test]
+    fn notify_statement_round_trips_through_extract_json_field() {
+        // A path with a tab and a carriage return — the exact shape the
+        // review flagged as producing invalid JSON before this fix.
+        let path = "kb1.weird\tpath\rhere";
+        let sql = notify_statement("status_table", path, "updated");
+        // `notify_statement` SQL-escapes the payload for the pg_notify
+        // literal; undo that the same way `sql_escape` applies it, to get
+        // back the raw JSON payload a subscriber would actually receive.
+        let payload = sql
+            .split("', '")
+            .nth(1)
+            .unwrap()
+            .trim_end_matches("')")
+            .replace("''", "'");
+
+        assert_eq!(extract_json_field(&payload, "path").as_deref(), Some(path));
+        assert_eq!(extract_json_field(&payload, "operation").as_deref(), Some("updated"));
+    }
+}
@@ -1,18 +1,24 @@
 
 use pq_sys::*;
 use std::ffi::{CStr, CString};
-use std::ptr;
-use std::thread;
-use std::time::Duration;
 
-#[derive(Clone)]
+use super::kb_prepared_cache::PreparedStatementCache;
+use super::kb_sql_state::{classify_sqlstate, retry_or_give_up, KbError};
+use super::kb_status_notify::notify_statement;
+use super::kb_wire_format::{read_column, WireFormat};
+
+#[derive(Clone, Default)]
 pub struct StatusDataContext {
     pub kb_search: *mut PGconn,
     pub base_table: String,
+    pub prepared: PreparedStatementCache,
+    /// Wire format for the `data` column — `Text` by default, or `Binary`
+    /// to cut bandwidth for large stream/status payloads.
+    pub format: WireFormat,
 }
 
 pub fn get_status_data(
-    ctx: &StatusDataContext,
+    ctx: &mut StatusDataContext,
     path: &str,
     data_str: &mut String,
 ) -> i32 {
@@ -22,31 +28,32 @@ pub fn get_status_data(
     }
 
     let query_buf = format!("SELECT data FROM {} WHERE path = $1 LIMIT 1", ctx.base_table);
-    let c_query = unsafe {
-        let c_str = CString::new(query_buf).unwrap();
-        let path_cstr = CString::new(path).unwrap();
-        let param_values: [*const i8; 1] = [path_cstr.as_ptr()];
-        let param_lengths: [path.len() as i32];
-        let param_formats: [0i32];
-        PQexecParams(
-            ctx.kb_search,
-            c_str.as_ptr(),
-            1,
-            ptr::null(),
-            param_values.as_ptr(),
-            param_lengths.as_ptr(),
-            param_formats.as_ptr ptr::null(),
-            param_formats.as_ptr(),
-            0,
-        )
+    let path_cstr = CString::new(path).unwrap();
+    let param_values: [*const i8; 1] = [path_cstr.as_ptr()];
+
+    let base_table = ctx.base_table.clone();
+    let res = match ctx.prepared.exec_prepared_formatted(
+        ctx.kb_search,
+        &base_table,
+        "select",
+        &query_buf,
+        1,
+        &param_values,
+        &[],
+        &[],
+        ctx.format.result_format(),
+    ) {
+        Ok(res) => res,
+        Err(err) => {
+            eprintln!("Error executing query for path '{}': {:?}", path, err);
+            return -1;
+        }
     };
 
     if unsafe { PQresultStatus(res) } != PGRES_TUPLES_OK {
-        eprintln!(
-            "Error executing query: {}",
-            unsafe { CStr::from_ptr(PQresultErrorMessage(res)) }.to_string_lossy()
-        );
+        let classified = classify_sqlstate(res);
         unsafe { PQclear(res) };
+        eprintln!("Error executing query for path '{}': {:?}", path, classified);
         return -1;
     }
 
@@ -56,143 +63,151 @@ pub fn get_status_data(
         return -1;
     }
 
-    let data = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_string_lossy().to_string() };
+    let data = read_column(res, 0, 0, ctx.format);
     *data_str = data;
 
     unsafe { PQclear(res) };
     0
 }
 
+/// Upserts `data` at `path`, retrying transient failures (serialization
+/// conflicts, lock timeouts) up to `retry_count` times. Returns a
+/// human-readable description of what happened ("inserted"/"updated") on
+/// success, or the classified failure on exhaustion — replacing the old
+/// `-1`-return / `success`+`message` out-param convention.
 pub fn set_status_data(
-    ctx: &StatusDataContext,
+    ctx: &mut StatusDataContext,
     path: &str,
     data: &str,
     retry_count: i32,
     retry_delay: f64,
-    success: &mut i32,
-    message: &mut String,
-) -> i32 {
+) -> Result<String, KbError> {
     if path.is_empty() {
-        eprintln!("Path cannot be empty or NULL");
-        return -1;
+        return Err(KbError::Permanent("Path cannot be empty or NULL".to_string()));
     }
     if data.is_empty() {
-        eprintln!("Data cannot be empty or NULL");
-        return -1;
+        return Err(KbError::Permanent("Data cannot be empty or NULL".to_string()));
     }
-
     if retry_count < 0 {
-        eprintln!("Retry count must be non-negative");
-        return -1;
+        return Err(KbError::Permanent("Retry count must be non-negative".to_string()));
     }
-
     if retry_delay < 0.0 {
-        eprintln!("Retry delay must be non-negative");
-        return -1;
+        return Err(KbError::Permanent("Retry delay must be non-negative".to_string()));
     }
 
     let query_buf = format!(
         "INSERT INTO {} (path, data) VALUES ($1, $2) ON CONFLICT (path) DO UPDATE SET data = EXCLUDED.data RETURNING path, (xmax = 0) AS was_inserted",
         ctx.base_table
     );
-    let c_query = CString::new(query_buf).unwrap();
 
     let path_cstr = CString::new(path).unwrap();
-    let data_cstr = CString::new(data).unwrap();
-    let param_values: [*const i8; 2] = [path_cstr.as_ptr(), data_cstr.as_ptr()];
-    let param_lengths: [i32; 2] = [path.len() as i32 i32, data.len() as i32];
-    let param_formats: [0i32; 2] = [0, 0];
-
-    let mut last_error: Option<String> = None;
-    let mut attempt = 0;
-    let mut result = -1;
-
-    'retry: for a in 0..=retry_count {
-        attempt = a + 1;
-
+    // Binary-format jsonb isn't NUL-terminated the way text-format data is,
+    // so the encoded bytes (and their explicit length below) have to stay
+    // alive and get passed through to `PQexecPrepared` rather than handed
+    // off as a `CString`.
+    let data_bytes = ctx.format.encode_param(data);
+    let param_values: [*const i8; 2] = [path_cstr.as_ptr(), data_bytes.as_ptr() as *const i8];
+    let param_lengths = [0, data_bytes.len() as i32];
+    let base_table = ctx.base_table.clone();
+
+    for attempt in 0..=retry_count {
         let begin_str = CString::new("BEGIN").unwrap();
         let begin_res = unsafe { PQexec(ctx.kb_search, begin_str.as_ptr()) };
         if unsafe { PQresultStatus(begin_res) } != PGRES_COMMAND_OK {
-            eprintln!(
-                "Error starting transaction: {}",
-                unsafe { CStr::from_ptr(PQresultErrorMessage(begin_res)).to_string_lossy()
-            );
+            let classified = classify_sqlstate(begin_res);
             unsafe { PQclear(begin_res) };
-            return -1;
+            return Err(classified);
         }
         unsafe { PQclear(begin_res) };
 
-        let res = unsafe {
-            PQexecParams(
-                ctx.kb_search,
-                c_query.as_ptr(),
-                2,
-                ptr::null(),
-                param_values.as_ptr(),
-                param_lengths.as_ptr(),
-                param_formats.as_ptr(),
-                param_formats.as_ptr(),
-                0,
-            )
+        let param_formats = [0, ctx.format.param_format()];
+        let res = match ctx.prepared.exec_prepared_formatted(
+            ctx.kb_search,
+            &base_table,
+            "upsert",
+            &query_buf,
+            2,
+            &param_values,
+            &param_formats,
+            &param_lengths,
+            0,
+        ) {
+            Ok(res) => res,
+            Err(classified) => {
+                let rollback_str = CString::new("ROLLBACK").unwrap();
+                let rollback_res = unsafe { PQexec(ctx.kb_search, rollback_str.as_ptr()) };
+                unsafe { PQclear(rollback_res) };
+
+                match retry_or_give_up(classified, attempt + 1, retry_count + 1, retry_delay) {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
         };
         let status = unsafe { PQresultStatus(res) };
 
-        if status == PGRES_TUPLES_OK {
-            let n = unsafe { PQntuples(res) };
-            if n > 0 {
-                let returned_path = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_str().unwrap() };
-                let was_inserted = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 1)).to_str().unwrap() == "t" };
-                let operation = if was_inserted { "inserted" } else { "updated" };
-
-                let commit_str = CString::new("COMMIT").unwrap();
-                let commit_res = unsafe { PQexec(ctx.kb_search, commit_str.as_ptr()) };
-                if unsafe { PQresultStatus(commit_res) } != PGRES_COMMAND_OK {
-                    eprintln!(
-                        "Error committing transaction: {}",
-                        unsafe { CStr::from_ptr(PQresultErrorMessage(commit_res)) .to_string_lossy() }
-                    );
-                    unsafe { PQclear(commit_res) };
-                    unsafe { PQclear(res) };
-                    return -1;
-                }
-                unsafe { PQclear(commit_res) };
+        if status != PGRES_TUPLES_OK {
+            let classified = classify_sqlstate(res);
+            unsafe { PQclear(res) };
 
-                *success = 1;
-                message.clear();
-                message.push_str(&format!("Successfully {} data for path: {}", operation, returned_path));
+            let rollback_str = CString::new("ROLLBACK").unwrap();
+            let rollback_res = unsafe { PQexec(ctx.kb_search, rollback_str.as_ptr()) };
+            unsafe { PQclear(rollback_res) };
 
-                unsafe { PQclear(res) };
-                result = 0;
-                break 'retry;
-            } else {
-                let rollback_str = CString::new("ROLLBACK").unwrap();
-                let rollback_res = unsafe { PQexec(ctx.kb_search, rollback_str.as_ptr()) };
-                unsafe { PQclear(rollback_res) };
-                eprintln!("Database operation completed but no result was returned");
-                unsafe { PQclear(res) };
-                return -1;
+            match retry_or_give_up(classified, attempt + 1, retry_count + 1, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
-        } else {
-            last_error = Some(unsafe { CStr::from_ptr(PQresultErrorMessage(res)).to_string_lossy().to_string() });
+        }
+
+        if unsafe { PQntuples(res) } == 0 {
+            let rollback_str = CString::new("ROLLBACK").unwrap();
+            let rollback_res = unsafe { PQexec(ctx.kb_search, rollback_str.as_ptr()) };
+            unsafe { PQclear(rollback_res) };
             unsafe { PQclear(res) };
+            return Err(KbError::Permanent("Database operation completed but no result was returned".to_string()));
+        }
+
+        let returned_path = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_str().unwrap().to_string() };
+        let was_inserted = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 1)).to_str().unwrap() == "t" };
+        let operation = if was_inserted { "inserted" } else { "updated" };
+        unsafe { PQclear(res) };
+
+        // Notify any subscribers watching `<base_table>_status` inside the
+        // same transaction, so the change only becomes visible once committed.
+        let notify_sql = notify_statement(&base_table, &returned_path, operation);
+        let notify_c = CString::new(notify_sql).unwrap();
+        let notify_res = unsafe { PQexec(ctx.kb_search, notify_c.as_ptr()) };
+        if unsafe { PQresultStatus(notify_res) } != PGRES_TUPLES_OK {
+            let classified = classify_sqlstate(notify_res);
+            unsafe { PQclear(notify_res) };
 
             let rollback_str = CString::new("ROLLBACK").unwrap();
             let rollback_res = unsafe { PQexec(ctx.kb_search, rollback_str.as_ptr()) };
             unsafe { PQclear(rollback_res) };
 
-            if a < retry_count {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue 'retry;
-            } else {
-                *success = 0;
-                message.clear();
-                let err = last_error.as_ref().map(|s| s.as_str()).unwrap_or("Unknown error");
-                message.push_str(&format!("Failed to set status data for path '{}' after {} attempts: {}", path, retry_count + 1, err));
-                result = -1;
-                break;
+            match retry_or_give_up(classified, attempt + 1, retry_count + 1, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
         }
+        unsafe { PQclear(notify_res) };
+
+        let commit_str = CString::new("COMMIT").unwrap();
+        let commit_res = unsafe { PQexec(ctx.kb_search, commit_str.as_ptr()) };
+        if unsafe { PQresultStatus(commit_res) } != PGRES_COMMAND_OK {
+            let classified = classify_sqlstate(commit_res);
+            unsafe { PQclear(commit_res) };
+            return Err(classified);
+        }
+        unsafe { PQclear(commit_res) };
+
+        return Ok(format!("Successfully {} data for path: {}", operation, returned_path));
     }
 
-    result
+    Err(KbError::Permanent(format!(
+        "Failed to set status data for path '{}' after {} attempts",
+        path,
+        retry_count + 1
+    )))
 }
@@ -2,50 +2,10 @@
 use pq_sys::*;
 use std::ffi::{CStr, CString};
 use std::ptr;
-use std::thread;
-use std::time::Duration;
 
-fn print_error(error_msg: &mut Option<String>, message: Option<&str>) {
-    *error_msg = message.map(|s| s.to_string());
-}
-
-enum Action {
-    Continue,
-    ReturnMinusOne,
-}
-
-fn handle_inner_error(
-    attempt: i32,
-    max_retries: i32,
-    inner_error: &Option<String>,
-    path: &str,
-    error_msg: &mut Option<String>,
-    is_lock_fail: bool,
-) -> Action {
-    if let Some(err) = inner_error {
-        if err.contains("No records found") {
-            print_error(error_msg, Some(err));
-            Action::ReturnMinusOne
-        } else if attempt < max_retries {
-            Action::Continue
-        } else {
-            let msg = format!("Error pushing stream data for path '{}': {}", path, err);
-            print_error(error_msg, Some(&msg));
-            Action::ReturnMinusOne
-        }
-    } else if is_lock_fail {
-        if attempt < max_retries {
-            Action::Continue
-        } else {
-            let msg = format!("Could not lock any row for path='{}' after {} attempts", path, max_retries);
-            print_error(error_msg, Some(&msg));
-            Action::ReturnMinusOne
-        }
-    } else {
-        print_error(error_msg, Some("Unexpected error in push_stream_data"));
-        Action::ReturnMinusOne
-    }
-}
+use super::kb_prepared_cache::PreparedStatementCache;
+use super::kb_sql_state::{classify_sqlstate, retry_or_give_up, KbError};
+use super::kb_wire_format::WireFormat;
 
 fn rollback(conn: *mut PGconn) {
     let rb_str = CString::new("ROLLBACK").unwrap();
@@ -60,75 +20,55 @@ pub fn push_stream_data(
     data: &str,
     max_retries: i32,
     retry_delay: f64,
-    error_msg: &mut Option<String>,
-) -> i32 {
-    print_error(error_msg, None);
-
+    cache: &mut PreparedStatementCache,
+    format: WireFormat,
+) -> Result<(), KbError> {
     if path.is_empty() {
-        print_error(error_msg, Some("Path cannot be empty or None"));
-        return -1;
+        return Err(KbError::Permanent("Path cannot be empty or None".to_string()));
     }
 
     let path_cstr = CString::new(path).unwrap();
     let param_values: [*const i8; 1] = [path_cstr.as_ptr()];
 
-    let mut inner_error: Option<String>;
-
     for attempt in 1..=max_retries {
-        inner_error = None;
-
         let begin_str = CString::new("BEGIN").unwrap();
         let res = unsafe { PQexec(conn, begin_str.as_ptr()) };
         if unsafe { PQresultStatus(res) } != PGRES_COMMAND_OK {
-            let err = unsafe { CStr::from_ptr(PQerrorMessage(conn)).to_string_lossy().to_string() };
-            print_error(error_msg, Some(&err));
+            let classified = classify_sqlstate(res);
             unsafe { PQclear(res) };
-            return -1;
+            return Err(classified);
         }
         unsafe { PQclear(res) };
 
         // 1) ensure there's at least one record to update
         let query_buf = format!("SELECT COUNT(*) as count FROM {} WHERE path = $1", base_table);
-        let c_query = CString::new(query_buf).unwrap();
-        let res = unsafe {
-            PQexecParams(
-                conn,
-                c_query.as_ptr(),
-                1,
-                ptr::null(),
-                param_values.as_ptr(),
-                ptr::null(),
-                ptr::null(),
-                0,
-            )
+        let res = match cache.exec_prepared(conn, base_table, "count", &query_buf, 1, &param_values) {
+            Ok(res) => res,
+            Err(err) => {
+                rollback(conn);
+                match retry_or_give_up(err, attempt, max_retries, retry_delay) {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
         };
         if unsafe { PQresultStatus(res) } != PGRES_TUPLES_OK {
-            inner_error = Some(unsafe { CStr::from_ptr(PQerrorMessage(conn)).to_string_lossy().to_string() });
+            let classified = classify_sqlstate(res);
             unsafe { PQclear(res) };
             rollback(conn);
-            let action = handle_inner_error(attempt, max_retries, &inner_error, path, error_msg, false);
-            if let Action::Continue = action {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue;
-            } else {
-                return -1;
+            match retry_or_give_up(classified, attempt, max_retries, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
         }
         let total: i32 = unsafe { CStr::from_ptr(PQgetvalue(res, 0, 0)).to_str().unwrap().parse().unwrap() };
         unsafe { PQclear(res) };
         if total == 0 {
-            inner_error = Some(format!(
-                "No records found for path='{}'. Records must be pre-allocated for stream tables.",
-                path
-            ));
+            // Not a libpq error — the count query succeeded and simply found
+            // nothing. Records must be pre-allocated for stream tables, so
+            // this short-circuits as NoData rather than retrying.
             rollback(conn);
-            let action = handle_inner_error(attempt, max_retries, &inner_error, path, error_msg, false);
-            if let Action::Continue = action {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue;
-            } else {
-                return -1;
-            }
+            return Err(KbError::NoData);
         }
 
         // 2) try to lock the oldest record regardless of valid status (true circular buffer)
@@ -136,40 +76,34 @@ pub fn push_stream_data(
             "SELECT id FROM {} WHERE path = $1 ORDER BY recorded_at ASC FOR UPDATE SKIP LOCKED LIMIT 1",
             base_table
         );
-        let c_query = CString::new(query_buf).unwrap();
-        let res = unsafe {
-            PQexecParams(
-                conn,
-                c_query.as_ptr(),
-                1,
-                ptr::null(),
-                param_values.as_ptr(),
-                ptr::null(),
-                ptr::null(),
-                0,
-            )
+        let res = match cache.exec_prepared(conn, base_table, "lock_oldest", &query_buf, 1, &param_values) {
+            Ok(res) => res,
+            Err(err) => {
+                rollback(conn);
+                match retry_or_give_up(err, attempt, max_retries, retry_delay) {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
         };
         if unsafe { PQresultStatus(res) } != PGRES_TUPLES_OK {
-            inner_error = Some(unsafe { CStr::from_ptr(PQerrorMessage(conn)).to_string_lossy().to_string() });
+            let classified = classify_sqlstate(res);
             unsafe { PQclear(res) };
             rollback(conn);
-            let action = handle_inner_error(attempt, max_retries, &inner_error, path, error_msg, false);
-            if let Action::Continue = action {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue;
-            } else {
-                return -1;
+            match retry_or_give_up(classified, attempt, max_retries, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
         }
         if unsafe { PQntuples(res) } == 0 {
+            // Another worker holds the lock on every candidate row — not a
+            // libpq error either, just a contention race worth retrying.
             unsafe { PQclear(res) };
             rollback(conn);
-            let action = handle_inner_error(attempt, max_retries, &None, path, error_msg, true);
-            if let Action::Continue = action {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue;
-            } else {
-                return -1;
+            let msg = format!("Could not lock any row for path='{}'", path);
+            match retry_or_give_up(KbError::Transient(msg), attempt, max_retries, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
         }
 
@@ -181,37 +115,47 @@ pub fn push_stream_data(
             "UPDATE {} SET data = $1, recorded_at = NOW(), valid = TRUE WHERE id = $2 RETURNING id",
             base_table
         );
-        let c_query = CString::new(query_buf).unwrap();
-        let data_cstr = CString::new(data).unwrap();
+        // Binary-format jsonb isn't NUL-terminated the way text-format data
+        // is, so the encoded bytes (and their explicit length below) have
+        // to stay alive and get passed through to `PQexecPrepared` rather
+        // than handed off as a `CString`.
+        let data_bytes = format.encode_param(data);
         let record_id_cstr = CString::new(record_id).unwrap();
-        let update_params: [*const i8; 2] = [data_cstr.as_ptr(), record_id_cstr.as_ptr()];
-        let res = unsafe {
-            PQexecParams(
-                conn,
-                c_query.as_ptr(),
-                2,
-                ptr::null(),
-                update_params.as_ptr(),
-                ptr::null(),
-                ptr::null(),
-                0,
-            )
+        let update_params: [*const i8; 2] = [data_bytes.as_ptr() as *const i8, record_id_cstr.as_ptr()];
+        let param_formats = [format.param_format(), 0];
+        let param_lengths = [data_bytes.len() as i32, 0];
+        let res = match cache.exec_prepared_formatted(
+            conn,
+            base_table,
+            "update_returning",
+            &query_buf,
+            2,
+            &update_params,
+            &param_formats,
+            &param_lengths,
+            0,
+        ) {
+            Ok(res) => res,
+            Err(err) => {
+                rollback(conn);
+                match retry_or_give_up(err, attempt, max_retries, retry_delay) {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
         };
         let status = unsafe { PQresultStatus(res) };
         if status != PGRES_TUPLES_OK || unsafe { PQntuples(res) } != 1 {
-            inner_error = if status != PGRES_TUPLES_OK {
-                Some(unsafe { CStr::from_ptr(PQerrorMessage(conn)).to_string_lossy().to_string() })
+            let classified = if status != PGRES_TUPLES_OK {
+                classify_sqlstate(res)
             } else {
-                Some("Failed to update record".to_string())
+                KbError::Permanent("Failed to update record".to_string())
             };
             unsafe { PQclear(res) };
             rollback(conn);
-            let action = handle_inner_error(attempt, max_retries, &inner_error, path, error_msg, false);
-            if let Action::Continue = action {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue;
-            } else {
-                return -1;
+            match retry_or_give_up(classified, attempt, max_retries, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
         }
         unsafe { PQclear(res) };
@@ -219,22 +163,163 @@ pub fn push_stream_data(
         let commit_str = CString::new("COMMIT").unwrap();
         let res = unsafe { PQexec(conn, commit_str.as_ptr()) };
         if unsafe { PQresultStatus(res) } != PGRES_COMMAND_OK {
-            inner_error = Some(unsafe { CStr::from_ptr(PQerrorMessage(conn)).to_string_lossy().to_string() });
+            let classified = classify_sqlstate(res);
             unsafe { PQclear(res) };
             rollback(conn);
-            let action = handle_inner_error(attempt, max_retries, &inner_error, path, error_msg, false);
-            if let Action::Continue = action {
-                thread::sleep(Duration::from_secs_f64(retry_delay));
-                continue;
-            } else {
-                return -1;
+            match retry_or_give_up(classified, attempt, max_retries, retry_delay) {
+                Some(err) => return Err(err),
+                None => continue,
             }
         }
         unsafe { PQclear(res) };
 
-        return 0;
+        return Ok(());
+    }
+
+    Err(KbError::Permanent("Unexpected error in push_stream_data".to_string()))
+}
+
+/// Classifies one statement's result within a pipelined batch: a real
+/// libpq error is classified the same way as the single-path functions, a
+/// successful update touching zero rows is `NoData` (no pre-allocated
+/// record for that path), and anything else is `Ok`.
+fn classify_batch_result(res: *mut PGresult, path: &str) -> Result<(), KbError> {
+    if res.is_null() {
+        return Err(KbError::Permanent(format!("No result returned for path '{}'", path)));
+    }
+    if unsafe { PQresultStatus(res) } != PGRES_TUPLES_OK {
+        return Err(classify_sqlstate(res));
+    }
+    if unsafe { PQntuples(res) } == 0 {
+        return Err(KbError::NoData);
+    }
+    Ok(())
+}
+
+/// Pushes many `(path, data)` updates inside a single `BEGIN`/`COMMIT`,
+/// issuing the per-path oldest-record-locking update through libpq's
+/// pipeline mode (`PQenterPipelineMode` / `PQsendQueryPrepared` /
+/// `PQexitPipelineMode`) so results are collected after one flush instead
+/// of one synchronous round trip per path. `NoData` for an individual path
+/// (no pre-allocated record) does not abort the transaction — the rest of
+/// the batch still commits. A real libpq error aborts and rolls back the
+/// whole batch: a transient one (serialization failure, lock timeout)
+/// retries the entire batch up to `max_retries`; a permanent one fails the
+/// whole call, since by definition nothing in an aborted transaction can
+/// be trusted to have committed.
+pub fn push_stream_data_batch(
+    conn: *mut PGconn,
+    base_table: &str,
+    entries: &[(String, String)],
+    max_retries: i32,
+    retry_delay: f64,
+    cache: &mut PreparedStatementCache,
+) -> Result<Vec<Result<(), KbError>>, KbError> {
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let stmt_sql = format!(
+        "UPDATE {base} SET data = $1, recorded_at = NOW(), valid = TRUE \
+         WHERE id = (SELECT id FROM {base} WHERE path = $2 ORDER BY recorded_at ASC FOR UPDATE SKIP LOCKED LIMIT 1) \
+         RETURNING id",
+        base = base_table
+    );
+
+    for attempt in 1..=max_retries {
+        let begin_str = CString::new("BEGIN").unwrap();
+        let begin_res = unsafe { PQexec(conn, begin_str.as_ptr()) };
+        if unsafe { PQresultStatus(begin_res) } != PGRES_COMMAND_OK {
+            let classified = classify_sqlstate(begin_res);
+            unsafe { PQclear(begin_res) };
+            return Err(classified);
+        }
+        unsafe { PQclear(begin_res) };
+
+        let name = match cache.ensure_prepared(conn, base_table, "batch_update", &stmt_sql, 2) {
+            Ok(name) => name,
+            Err(err) => {
+                rollback(conn);
+                return Err(err);
+            }
+        };
+        let name_c = CString::new(name).unwrap();
+
+        let data_cstrs: Vec<CString> = entries.iter().map(|(_, data)| CString::new(data.as_str()).unwrap()).collect();
+        let path_cstrs: Vec<CString> = entries.iter().map(|(path, _)| CString::new(path.as_str()).unwrap()).collect();
+
+        unsafe { PQenterPipelineMode(conn) };
+
+        let mut send_failed = false;
+        for (data_c, path_c) in data_cstrs.iter().zip(path_cstrs.iter()) {
+            let params: [*const i8; 2] = [data_c.as_ptr(), path_c.as_ptr()];
+            let sent = unsafe {
+                PQsendQueryPrepared(conn, name_c.as_ptr(), 2, params.as_ptr(), ptr::null(), ptr::null(), 0)
+            };
+            if sent != 1 {
+                send_failed = true;
+                break;
+            }
+        }
+        if send_failed {
+            let msg = unsafe { CStr::from_ptr(PQerrorMessage(conn)) }.to_string_lossy().to_string();
+            unsafe { PQexitPipelineMode(conn) };
+            rollback(conn);
+            return Err(KbError::Permanent(msg));
+        }
+
+        unsafe { PQpipelineSync(conn) };
+
+        let mut results: Vec<Result<(), KbError>> = Vec::with_capacity(entries.len());
+        for (path, _) in entries {
+            let res = unsafe { PQgetResult(conn) };
+            let outcome = classify_batch_result(res, path);
+            if !res.is_null() {
+                unsafe { PQclear(res) };
+            }
+            // a statement's results end with a NULL sentinel in pipeline mode too
+            let sentinel = unsafe { PQgetResult(conn) };
+            if !sentinel.is_null() {
+                unsafe { PQclear(sentinel) };
+            }
+            results.push(outcome);
+        }
+        // consume the PQpipelineSync marker
+        let sync_res = unsafe { PQgetResult(conn) };
+        if !sync_res.is_null() {
+            unsafe { PQclear(sync_res) };
+        }
+        unsafe { PQexitPipelineMode(conn) };
+
+        let worst = results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .filter(|err| !matches!(err, KbError::NoData))
+            .cloned()
+            .next();
+
+        match worst {
+            None => {
+                let commit_str = CString::new("COMMIT").unwrap();
+                let commit_res = unsafe { PQexec(conn, commit_str.as_ptr()) };
+                if unsafe { PQresultStatus(commit_res) } != PGRES_COMMAND_OK {
+                    let classified = classify_sqlstate(commit_res);
+                    unsafe { PQclear(commit_res) };
+                    rollback(conn);
+                    return Err(classified);
+                }
+                unsafe { PQclear(commit_res) };
+                return Ok(results);
+            }
+            Some(err) => {
+                rollback(conn);
+                match retry_or_give_up(err, attempt, max_retries, retry_delay) {
+                    Some(err) => return Err(err),
+                    None => continue,
+                }
+            }
+        }
     }
 
-    print_error(error_msg, Some("Unexpected error in push_stream_data"));
-    -1
+    Err(KbError::Permanent("Unexpected error in push_stream_data_batch".to_string()))
 }
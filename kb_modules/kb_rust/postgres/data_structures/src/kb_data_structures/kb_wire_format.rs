@@ -0,0 +1,86 @@
+
+use pq_sys::*;
+use std::ffi::CStr;
+
+/// `jsonb_send`/`jsonb_recv`'s wire-format version byte: a binary-format
+/// jsonb value is this one byte followed by the same UTF-8 text a text-mode
+/// column would carry. `data` is always jsonb in this module, so `Binary`
+/// mode is scoped to that encoding rather than a generic byte passthrough.
+const JSONB_BINARY_VERSION: u8 = 1;
+
+/// Selects libpq's wire format for a `data` column: `Text` (the default,
+/// NUL-terminated and stringified by the server) or `Binary` (jsonb's
+/// version-byte-prefixed binary form, read back via `PQgetvalue` +
+/// `PQgetlength` instead of `CStr`). Binary cuts bandwidth and CPU for
+/// large stream/status payloads, at the cost of losing the free
+/// human-readability of text mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    #[default]
+    Text,
+    Binary,
+}
+
+impl WireFormat {
+    /// The `resultFormat` argument `PQexecParams`/`PQexecPrepared` expect.
+    pub fn result_format(self) -> i32 {
+        match self {
+            WireFormat::Text => 0,
+            WireFormat::Binary => 1,
+        }
+    }
+
+    /// The per-parameter format code this wire format corresponds to.
+    pub fn param_format(self) -> i32 {
+        match self {
+            WireFormat::Text => 0,
+            WireFormat::Binary => 1,
+        }
+    }
+
+    /// Encodes `data` (jsonb text) as the bytes this wire format actually
+    /// sends. Text mode passes the JSON text through unchanged plus a
+    /// trailing NUL, since libpq reads text-format parameters up to the
+    /// first NUL; binary mode prepends jsonb's version byte. Callers must send the returned
+    /// length explicitly alongside the corresponding `param_format` —
+    /// unlike text-format parameters, a binary-format parameter is not
+    /// NUL-scanned by libpq, so a wrong/omitted length silently truncates
+    /// or misreads it.
+    pub fn encode_param(self, data: &str) -> Vec<u8> {
+        match self {
+            WireFormat::Text => {
+                let mut encoded = Vec::with_capacity(data.len() + 1);
+                encoded.extend_from_slice(data.as_bytes());
+                encoded.push(0);
+                encoded
+            }
+            WireFormat::Binary => {
+                let mut encoded = Vec::with_capacity(data.len() + 1);
+                encoded.push(JSONB_BINARY_VERSION);
+                encoded.extend_from_slice(data.as_bytes());
+                encoded
+            }
+        }
+    }
+}
+
+/// Reads column `col` of row `row` out of `res` according to `format`.
+/// Text mode trusts the server's NUL-terminated string; binary mode uses
+/// `PQgetlength` (since a binary payload isn't NUL-terminated) and skips
+/// the leading jsonb version byte `encode_param` prepended on the way in —
+/// `jsonb_recv` itself is what rejects an unexpected version, so this does
+/// not re-validate it.
+pub fn read_column(res: *mut PGresult, row: i32, col: i32, format: WireFormat) -> String {
+    match format {
+        WireFormat::Text => unsafe { CStr::from_ptr(PQgetvalue(res, row, col)) }.to_string_lossy().to_string(),
+        WireFormat::Binary => unsafe {
+            let ptr = PQgetvalue(res, row, col) as *const u8;
+            let len = PQgetlength(res, row, col) as usize;
+            if len == 0 {
+                return String::new();
+            }
+            let bytes = std::slice::from_raw_parts(ptr, len);
+            String::from_utf8_lossy(&bytes[1..]).to_string()
+        },
+    }
+}
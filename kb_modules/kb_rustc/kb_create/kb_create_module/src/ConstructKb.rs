@@ -3,7 +3,7 @@
 use std::collections::HashMap;
 use serde_json::Value;
 use postgres::Error;
-use base_construct_kb::KnowledgeBaseManager;
+use base_construct_kb::{KbPool, KnowledgeBaseManager};
 
 /// Builds on KnowledgeBaseManager to maintain header/info stacks per KB.
 pub struct ConstructKb {
@@ -38,6 +38,19 @@ impl ConstructKb {
         })
     }
 
+    /// Checks out a client from `pool` instead of opening a dedicated
+    /// connection, so one pool can back many concurrent `ConstructKb`
+    /// header/info stack sessions against the same database.
+    pub fn from_pool(pool: &KbPool, table_name: &str) -> Result<Self, Error> {
+        let mgr = KnowledgeBaseManager::from_pool(pool, table_name)?;
+        Ok(Self {
+            inner: mgr,
+            path: HashMap::new(),
+            path_values: HashMap::new(),
+            working_kb: None,
+        })
+    }
+
     /// Expose the raw client & transaction if you need it.
     pub fn get_db_objects(&mut self) -> (&mut postgres::Client, &mut postgres::Transaction) {
         self.inner.get_db_objects()
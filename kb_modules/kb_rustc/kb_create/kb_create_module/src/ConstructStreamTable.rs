@@ -1,8 +1,97 @@
 use std::collections::HashMap;
+use chrono::{DateTime, Utc};
 use postgres::{Client, Error, NoTls, types::Json, Row};
 use serde_json::Value;
+use uuid::Uuid;
 use crate::ConstructKb;
 
+/// One stream-table row as returned by `batch_read`/`read_range`.
+#[derive(Debug, Clone)]
+pub struct StreamEntry {
+    pub path: String,
+    pub recorded_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// A page of `read_range` results plus an opaque `(path, recorded_at)`
+/// cursor for resuming the scan, so large subtrees can be streamed without
+/// loading everything at once.
+#[derive(Debug, Clone)]
+pub struct StreamPage {
+    pub entries: Vec<StreamEntry>,
+    pub next: Option<(String, DateTime<Utc>)>,
+}
+
+/// One versioned schema migration: a name (logged to stderr when the step
+/// is applied) and the forward SQL it applies.
+struct Migration {
+    name: &'static str,
+    sql: String,
+}
+
+/// A claimed job-queue row, as returned by `claim_next`.
+#[derive(Debug, Clone)]
+pub struct JobRecord {
+    pub id: Uuid,
+    pub queue: String,
+    pub job: Value,
+    pub status: String,
+    pub attempts: i32,
+    pub heartbeat: Option<DateTime<Utc>>,
+}
+
+/// Maps a `postgres::Row` into a typed struct, so reads go through
+/// `query_as` instead of ad-hoc positional `row.get(i)` calls and silent
+/// coercions like `unwrap_or(0)`.
+trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, Error>;
+}
+
+/// A raw stream-table row, as stored (before being trimmed down to a
+/// `StreamEntry` for callers).
+#[derive(Debug, Clone)]
+pub struct StreamRow {
+    pub id: i32,
+    pub path: String,
+    pub recorded_at: DateTime<Utc>,
+    pub valid: bool,
+    pub data: Value,
+}
+
+impl FromRow for StreamRow {
+    fn from_row(row: &Row) -> Result<Self, Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            path: row.try_get(1)?,
+            recorded_at: row.try_get(2)?,
+            valid: row.try_get(3)?,
+            data: row.try_get::<_, Json<Value>>(4)?.0,
+        })
+    }
+}
+
+/// A KB-defined stream field (`path`, `stream_length`), as listed by
+/// `list_stream_field_defs`.
+#[derive(Debug, Clone)]
+pub struct StreamFieldDef {
+    pub path: String,
+    pub stream_length: i32,
+}
+
+impl FromRow for StreamFieldDef {
+    fn from_row(row: &Row) -> Result<Self, Error> {
+        let path: String = row.try_get(0)?;
+        let raw: String = row.try_get(1)?;
+        let stream_length: i32 = raw.parse().map_err(|e| {
+            Error::from(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("stream_length for '{}' is not an integer: {}", path, e),
+            ))
+        })?;
+        Ok(Self { path, stream_length })
+    }
+}
+
 /// Manages a stream table tied to a ConstructKb, setting up schema and
 /// synchronizing stream entries based on KB-defined stream fields.
 pub struct ConstructStreamTable {
@@ -21,45 +110,226 @@ impl ConstructStreamTable {
     ) -> Result<Self, Error> {
         let table_name = format!("{}_stream", database);
         let mut inst = ConstructStreamTable { client, construct_kb, database: database.into(), table_name };
-        inst.setup_schema()?;
+        inst.migrate()?;
         Ok(inst)
     }
 
-    fn setup_schema(&mut self) -> Result<(), Error> {
-        // Drop existing
-        let drop = format!("DROP TABLE IF EXISTS \"{}\" CASCADE;", self.table_name);
-        self.client.batch_execute(&drop)?;
-
-        // Ensure ltree
-        self.client.batch_execute("CREATE EXTENSION IF NOT EXISTS ltree;")?;
+    /// Reads `kb_schema_version`, applies only the migrations that haven't
+    /// run yet (each inside its own transaction), and bumps the version.
+    /// This is the default, non-destructive setup path for both fresh
+    /// databases and upgrades of an existing one.
+    pub fn migrate(&mut self) -> Result<(), Error> {
+        self.client
+            .batch_execute("CREATE TABLE IF NOT EXISTS kb_schema_version (version INT NOT NULL);")?;
+        let current: i32 = match self.client.query_opt("SELECT version FROM kb_schema_version;", &[])? {
+            Some(row) => row.get(0),
+            None => {
+                self.client
+                    .execute("INSERT INTO kb_schema_version (version) VALUES (0);", &[])?;
+                0
+            }
+        };
 
-        // Drop again (optional mirror of Python)
-        self.client.batch_execute(&drop)?;
+        for (i, step) in Self::migrations(&self.database, &self.table_name).iter().enumerate() {
+            let version = (i + 1) as i32;
+            if version <= current {
+                continue;
+            }
+            eprintln!("applying migration {}: {}", version, step.name);
+            let mut txn = self.client.transaction()?;
+            txn.batch_execute(&step.sql)?;
+            txn.execute("UPDATE kb_schema_version SET version = $1;", &[&version])?;
+            txn.commit()?;
+        }
+        Ok(())
+    }
 
-        // Create table
-        let create = format!(r#"
-            CREATE TABLE "{}" (
-                id SERIAL PRIMARY KEY,
-                path LTREE,
-                recorded_at TIMESTAMPTZ DEFAULT NOW(),
-                valid BOOLEAN DEFAULT FALSE,
-                data JSONB
-            );
-        "#, self.table_name);
-        self.client.batch_execute(&create)?;
-
-        // Indexes
-        let idxs = vec![
-            format!("CREATE INDEX IF NOT EXISTS idx_{}_path_gist ON \"{}\" USING GIST(path);", self.table_name, self.table_name),
-            format!("CREATE INDEX IF NOT EXISTS idx_{}_path_btree ON \"{}\"(path);", self.table_name, self.table_name),
-            format!("CREATE INDEX IF NOT EXISTS idx_{}_recorded_at ON \"{}\"(recorded_at);", self.table_name, self.table_name),
-            format!("CREATE INDEX IF NOT EXISTS idx_{}_recorded_at_desc ON \"{}\"(recorded_at DESC);", self.table_name, self.table_name),
-            format!("CREATE INDEX IF NOT EXISTS idx_{}_path_recorded_at ON \"{}\"(path, recorded_at);", self.table_name, self.table_name),
+    /// Drops every KB and stream table and re-applies all migrations from
+    /// scratch — the old unconditional `setup_schema` behavior, now opt-in
+    /// since it destroys existing data.
+    pub fn recreate(&mut self) -> Result<(), Error> {
+        let drops = [
+            format!("DROP TABLE IF EXISTS \"{}\" CASCADE;", self.table_name),
+            format!("DROP TABLE IF EXISTS \"{}\" CASCADE;", self.job_queue_table()),
+            format!("DROP TABLE IF EXISTS \"{}\" CASCADE;", self.database),
+            format!("DROP TABLE IF EXISTS \"{}_info\" CASCADE;", self.database),
+            format!("DROP TABLE IF EXISTS \"{}_link\" CASCADE;", self.database),
+            format!("DROP TABLE IF EXISTS \"{}_link_mount\" CASCADE;", self.database),
+            "DROP TABLE IF EXISTS kb_schema_version CASCADE;".to_string(),
         ];
-        for sql in idxs { self.client.batch_execute(&sql)?; }
+        for drop in &drops {
+            self.client.batch_execute(drop)?;
+        }
+        self.migrate()
+    }
+
+    /// The ordered list of forward migrations. Migration 1 is the initial
+    /// ltree schema (info/node/link/link_mount/stream tables + indexes), so
+    /// a fresh database and an upgrade from nothing share this one code path.
+    fn migrations(database: &str, stream_table: &str) -> Vec<Migration> {
+        vec![Migration {
+            name: "001_initial_schema",
+            sql: format!(
+                r#"
+                CREATE EXTENSION IF NOT EXISTS ltree;
+
+                CREATE TABLE IF NOT EXISTS "{database}_info" (
+                    knowledge_base TEXT PRIMARY KEY,
+                    description TEXT
+                );
+
+                CREATE TABLE IF NOT EXISTS "{database}" (
+                    knowledge_base TEXT NOT NULL,
+                    label TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    properties JSONB,
+                    data JSONB,
+                    has_link BOOLEAN DEFAULT FALSE,
+                    has_link_mount BOOLEAN DEFAULT FALSE,
+                    path LTREE PRIMARY KEY
+                );
+                CREATE INDEX IF NOT EXISTS idx_{database}_path_gist ON "{database}" USING GIST(path);
+
+                CREATE TABLE IF NOT EXISTS "{database}_link" (
+                    id SERIAL PRIMARY KEY,
+                    parent_node_kb TEXT NOT NULL,
+                    parent_path LTREE NOT NULL,
+                    link_name TEXT NOT NULL
+                );
+
+                CREATE TABLE IF NOT EXISTS "{database}_link_mount" (
+                    id SERIAL PRIMARY KEY,
+                    link_name TEXT NOT NULL,
+                    knowledge_base TEXT NOT NULL,
+                    mount_path LTREE NOT NULL,
+                    description TEXT
+                );
+
+                CREATE TABLE IF NOT EXISTS "{stream_table}" (
+                    id SERIAL PRIMARY KEY,
+                    path LTREE,
+                    recorded_at TIMESTAMPTZ DEFAULT NOW(),
+                    valid BOOLEAN DEFAULT FALSE,
+                    data JSONB
+                );
+                CREATE INDEX IF NOT EXISTS idx_{stream_table}_path_gist ON "{stream_table}" USING GIST(path);
+                CREATE INDEX IF NOT EXISTS idx_{stream_table}_path_btree ON "{stream_table}"(path);
+                CREATE INDEX IF NOT EXISTS idx_{stream_table}_recorded_at ON "{stream_table}"(recorded_at);
+                CREATE INDEX IF NOT EXISTS idx_{stream_table}_recorded_at_desc ON "{stream_table}"(recorded_at DESC);
+                CREATE INDEX IF NOT EXISTS idx_{stream_table}_path_recorded_at ON "{stream_table}"(path, recorded_at);
+                "#
+            ),
+        },
+        Migration {
+            name: "002_job_queue",
+            sql: {
+                let job_queue_table = format!("{}_job_queue", stream_table);
+                format!(
+                    r#"
+                    DO $$ BEGIN
+                        CREATE TYPE job_status AS ENUM ('new', 'running');
+                    EXCEPTION WHEN duplicate_object THEN NULL;
+                    END $$;
+
+                    CREATE TABLE IF NOT EXISTS "{job_queue_table}" (
+                        id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+                        queue VARCHAR NOT NULL,
+                        job JSONB NOT NULL,
+                        status job_status NOT NULL DEFAULT 'new',
+                        attempts INT NOT NULL DEFAULT 0,
+                        heartbeat TIMESTAMPTZ
+                    );
+                    CREATE INDEX IF NOT EXISTS idx_{job_queue_table}_queue_status ON "{job_queue_table}"(queue, status);
+                    "#
+                )
+            },
+        }]
+    }
+
+    /// The name of the job-queue table backing `enqueue`/`claim_next`.
+    fn job_queue_table(&self) -> String {
+        format!("{}_job_queue", self.table_name)
+    }
+
+    /// Enqueues `job` onto `queue` for a worker to pick up via `claim_next`.
+    pub fn enqueue(&mut self, queue: &str, job: Value) -> Result<(), Error> {
+        let table = self.job_queue_table();
+        let stmt = format!(
+            "INSERT INTO \"{}\" (queue, job, status, attempts) VALUES ($1, $2, 'new', 0);",
+            table
+        );
+        self.client.execute(stmt.as_str(), &[&queue, &Json(&job)])?;
+        Ok(())
+    }
+
+    /// Atomically claims one `new` job from `queue` (or a `running` one
+    /// whose heartbeat is older than `worker_timeout_secs` — i.e. a crashed
+    /// worker's job), marking it `running` with a fresh heartbeat. Uses
+    /// `FOR UPDATE SKIP LOCKED` so concurrent workers never claim the same row.
+    pub fn claim_next(&mut self, queue: &str, worker_timeout_secs: i64) -> Result<Option<JobRecord>, Error> {
+        let table = self.job_queue_table();
+        let stmt = format!(
+            r#"
+            UPDATE "{table}" SET status = 'running', heartbeat = NOW()
+            WHERE id = (
+                SELECT id FROM "{table}"
+                WHERE queue = $1
+                  AND (status = 'new' OR (status = 'running' AND heartbeat < NOW() - ($2 || ' seconds')::interval))
+                ORDER BY id
+                FOR UPDATE SKIP LOCKED
+                LIMIT 1
+            )
+            RETURNING id, queue, job, status::text, attempts, heartbeat;
+            "#
+        );
+        let timeout = worker_timeout_secs.to_string();
+        let row = self.client.query_opt(stmt.as_str(), &[&queue, &timeout])?;
+        Ok(row.map(|r| JobRecord {
+            id: r.get(0),
+            queue: r.get(1),
+            job: r.get::<_, Json<Value>>(2).0,
+            status: r.get(3),
+            attempts: r.get(4),
+            heartbeat: r.get(5),
+        }))
+    }
+
+    /// Extends a claimed job's lease so other workers don't treat it as crashed.
+    pub fn heartbeat(&mut self, id: Uuid) -> Result<(), Error> {
+        let table = self.job_queue_table();
+        let stmt = format!("UPDATE \"{}\" SET heartbeat = NOW() WHERE id = $1;", table);
+        self.client.execute(stmt.as_str(), &[&id])?;
         Ok(())
     }
 
+    /// Marks a job done, removing it from the queue.
+    pub fn complete(&mut self, id: Uuid) -> Result<(), Error> {
+        let table = self.job_queue_table();
+        let stmt = format!("DELETE FROM \"{}\" WHERE id = $1;", table);
+        self.client.execute(stmt.as_str(), &[&id])?;
+        Ok(())
+    }
+
+    /// Marks a job failed, incrementing its attempt count and releasing it
+    /// back to `new` so another worker (or a retry) can claim it.
+    pub fn fail(&mut self, id: Uuid) -> Result<(), Error> {
+        let table = self.job_queue_table();
+        let stmt = format!(
+            "UPDATE \"{}\" SET status = 'new', attempts = attempts + 1 WHERE id = $1;",
+            table
+        );
+        self.client.execute(stmt.as_str(), &[&id])?;
+        Ok(())
+    }
+
+    /// Queues `path` for background validation/ingestion on the
+    /// `"stream_validation"` queue, so `manage_stream_table`'s placeholder
+    /// rows can be filled in by workers calling `claim_next` instead of only
+    /// synchronously, with no risk of two workers double-processing the same path.
+    pub fn queue_validation(&mut self, path: &str) -> Result<(), Error> {
+        self.enqueue("stream_validation", serde_json::json!({ "path": path }))
+    }
+
     /// Defines a new stream field in KB and returns a summary JSON.
     pub fn add_stream_field(
         &mut self,
@@ -89,6 +359,127 @@ impl ConstructStreamTable {
         }))
     }
 
+    /// Writes many stream rows in one multi-VALUES statement per chunk,
+    /// mirroring `remove_invalid_stream_fields`'s chunking so large batches
+    /// don't blow past Postgres's bind-parameter limit.
+    pub fn batch_insert(&mut self, entries: &[(String, Value)], chunk_size: usize) -> Result<(), Error> {
+        if entries.is_empty() { return Ok(()); }
+
+        for chunk in entries.chunks(chunk_size) {
+            let jsons: Vec<Json<&Value>> = chunk.iter().map(|(_, data)| Json(data)).collect();
+
+            let placeholders: Vec<String> = (0..chunk.len())
+                .map(|i| format!("(${}, NOW(), ${}, TRUE)", i * 2 + 1, i * 2 + 2))
+                .collect();
+            let stmt = format!(
+                "INSERT INTO \"{}\" (path, recorded_at, data, valid) VALUES {};",
+                self.table_name,
+                placeholders.join(",")
+            );
+
+            let mut params: Vec<&(dyn postgres::types::ToSql + Sync)> = Vec::with_capacity(chunk.len() * 2);
+            for (i, (path, _)) in chunk.iter().enumerate() {
+                params.push(path as &(dyn postgres::types::ToSql + Sync));
+                params.push(&jsons[i] as &(dyn postgres::types::ToSql + Sync));
+            }
+            self.client.execute(stmt.as_str(), &params)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `sql` and maps every returned row into `T`, so reads get a
+    /// typed `Result` per row instead of positional `row.get(i)` calls.
+    fn query_as<T: FromRow>(&mut self, sql: &str, params: &[&(dyn postgres::types::ToSql + Sync)]) -> Result<Vec<T>, Error> {
+        let rows = self.client.query(sql, params)?;
+        rows.iter().map(T::from_row).collect()
+    }
+
+    /// Returns the latest valid row for each of `paths`, keyed by path.
+    pub fn batch_read(&mut self, paths: &[String]) -> Result<HashMap<String, StreamEntry>, Error> {
+        if paths.is_empty() { return Ok(HashMap::new()); }
+
+        let placeholders: Vec<String> = (1..=paths.len()).map(|i| format!("${}", i)).collect();
+        let stmt = format!(
+            "SELECT DISTINCT ON (path) id, path::text, recorded_at, valid, data FROM \"{}\" \
+             WHERE path IN ({}) AND valid = TRUE ORDER BY path, recorded_at DESC;",
+            self.table_name,
+            placeholders.join(",")
+        );
+        let params: Vec<&(dyn postgres::types::ToSql + Sync)> =
+            paths.iter().map(|p| p as &(dyn postgres::types::ToSql + Sync)).collect();
+
+        let rows: Vec<StreamRow> = self.query_as(stmt.as_str(), &params)?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                (
+                    r.path.clone(),
+                    StreamEntry { path: r.path, recorded_at: r.recorded_at, data: r.data },
+                )
+            })
+            .collect())
+    }
+
+    /// Returns every entry whose `path` descends from `prefix` (via the
+    /// ltree `<@` operator, so the existing GIST/btree indexes serve it),
+    /// optionally bounded to `[start, end]` and resumed from `after`'s
+    /// cursor, ordered by `recorded_at` (descending when `reverse`).
+    pub fn read_range(
+        &mut self,
+        prefix: &str,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        after: Option<(String, DateTime<Utc>)>,
+        limit: i64,
+        reverse: bool,
+    ) -> Result<StreamPage, Error> {
+        let mut clauses = vec!["path <@ $1::ltree".to_string()];
+        let mut idx = 2;
+        if start.is_some() {
+            clauses.push(format!("recorded_at >= ${}", idx));
+            idx += 1;
+        }
+        if end.is_some() {
+            clauses.push(format!("recorded_at <= ${}", idx));
+            idx += 1;
+        }
+        if after.is_some() {
+            // `batch_insert` writes many rows per statement off a single
+            // NOW(), so rows routinely tie on recorded_at; row-comparing
+            // against (recorded_at, path) instead of recorded_at alone
+            // breaks those ties instead of silently dropping/repeating rows
+            // at a page boundary.
+            let cmp = if reverse { "<" } else { ">" };
+            clauses.push(format!("(recorded_at, path::text) {} (${}, ${})", cmp, idx, idx + 1));
+        }
+
+        let order = if reverse { "DESC" } else { "ASC" };
+        let stmt = format!(
+            "SELECT id, path::text, recorded_at, valid, data FROM \"{}\" WHERE {} ORDER BY recorded_at {}, path {} LIMIT {};",
+            self.table_name,
+            clauses.join(" AND "),
+            order,
+            order,
+            limit
+        );
+
+        let mut params: Vec<&(dyn postgres::types::ToSql + Sync)> = vec![&prefix];
+        if let Some(ts) = start.as_ref() { params.push(ts); }
+        if let Some(ts) = end.as_ref() { params.push(ts); }
+        if let Some((after_path, after_ts)) = after.as_ref() {
+            params.push(after_ts);
+            params.push(after_path);
+        }
+
+        let rows: Vec<StreamRow> = self.query_as(stmt.as_str(), &params)?;
+        let entries: Vec<StreamEntry> = rows
+            .into_iter()
+            .map(|r| StreamEntry { path: r.path, recorded_at: r.recorded_at, data: r.data })
+            .collect();
+        let next = entries.last().map(|e| (e.path.clone(), e.recorded_at));
+        Ok(StreamPage { entries, next })
+    }
+
     /// Remove all entries whose path matches any in `invalid_paths`, in chunks.
     pub fn remove_invalid_stream_fields(
         &mut self,
@@ -152,30 +543,32 @@ impl ConstructStreamTable {
         Ok(())
     }
 
-    /// Synchronize stream table with KB definitions.
-    pub fn check_installation(&mut self) -> Result<(), Error> {
-        // 1) fetch distinct stream paths
-        let p = format!("SELECT DISTINCT path::text FROM \"{}\";", self.table_name);
-        let rows: Vec<Row> = self.client.query(&p, &[])?;
-        let unique_paths: Vec<String> = rows.iter().map(|r| r.get(0)).collect();
-
-        // 2) fetch KB-defined fields
-        let kq = format!(
-            "SELECT path, properties->>'stream_length' as sl FROM \"{}\" WHERE label='KB_STREAM_FIELD';",
+    /// Returns every distinct path currently present in the stream table.
+    pub fn list_distinct_stream_paths(&mut self) -> Result<Vec<String>, Error> {
+        let stmt = format!("SELECT DISTINCT path::text FROM \"{}\";", self.table_name);
+        self.client.query(stmt.as_str(), &[])?.iter().map(|r| r.try_get(0)).collect()
+    }
+
+    /// Lists the stream fields this KB defines (`KB_STREAM_FIELD` nodes),
+    /// with `stream_length` parsed and a typed error on malformed data
+    /// rather than silently coercing to `0`.
+    pub fn list_stream_field_defs(&mut self) -> Result<Vec<StreamFieldDef>, Error> {
+        let stmt = format!(
+            "SELECT path, properties->>'stream_length' FROM \"{}\" WHERE label = 'KB_STREAM_FIELD';",
             self.database
         );
-        let krows: Vec<Row> = self.client.query(&kq, &[])?;
-        let mut specified_paths = Vec::new();
-        let mut specified_lengths = Vec::new();
-        for r in krows {
-            let p: String = r.get(0);
-            let sl: i32 = r.get::<_, String>(1).parse().unwrap_or(0);
-            specified_paths.push(p);
-            specified_lengths.push(sl);
-        }
+        self.query_as(stmt.as_str(), &[])
+    }
+
+    /// Synchronize stream table with KB definitions.
+    pub fn check_installation(&mut self) -> Result<(), Error> {
+        let unique_paths = self.list_distinct_stream_paths()?;
+        let field_defs = self.list_stream_field_defs()?;
+
+        let specified_paths: Vec<String> = field_defs.iter().map(|d| d.path.clone()).collect();
+        let specified_lengths: Vec<i32> = field_defs.iter().map(|d| d.stream_length).collect();
 
-        // 3) diff
-        let invalid: Vec<String> = unique_paths.iter().filter(|p| !specified_paths.contains(p)).cloned().collect();
+        let invalid: Vec<String> = unique_paths.into_iter().filter(|p| !specified_paths.contains(p)).collect();
 
         self.remove_invalid_stream_fields(&invalid, 500)?;
         self.manage_stream_table(&specified_paths, &specified_lengths)?;
@@ -1,14 +1,104 @@
-```rust
 // src/lib.rs
 
+use deadpool_postgres::{Config, ManagerConfig, Pool, RecyclingMethod, Runtime};
 use tokio_postgres::{Client, NoTls, Error, Row};
 use tokio_postgres::types::Json;
 use tokio::spawn;
 use serde_json::Value;
 
+/// A connection either owned outright (one dedicated client per manager, the
+/// original construction path) or checked out of a [`KbPool`] for the life of
+/// this manager.
+enum Conn {
+    Owned(Client),
+    Pooled(deadpool_postgres::Client),
+}
+
+impl Conn {
+    fn get(&self) -> &Client {
+        match self {
+            Conn::Owned(client) => client,
+            Conn::Pooled(client) => client,
+        }
+    }
+
+    fn get_mut(&mut self) -> &mut Client {
+        match self {
+            Conn::Owned(client) => client,
+            Conn::Pooled(client) => client,
+        }
+    }
+}
+
+/// A shared `deadpool-postgres` pool, built from the same conn-string
+/// parameters `KnowledgeBaseManager::new` takes, so a service can hand out
+/// pooled clients to many managers (and `ConstructKb` sessions) instead of
+/// opening one dedicated connection each.
+pub struct KbPool {
+    pool: Pool,
+}
+
+impl KbPool {
+    /// Builds a pool against `conn_str`, recycling connections with a fast
+    /// verify-on-checkout and capping at `max_size` concurrent connections.
+    pub fn new(conn_str: &str, max_size: usize) -> Result<Self, Error> {
+        let mut cfg = Config::new();
+        cfg.url = Some(conn_str.to_string());
+        cfg.manager = Some(ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        });
+        cfg.pool = Some(deadpool_postgres::PoolConfig::new(max_size));
+
+        let pool = cfg
+            .create_pool(Some(Runtime::Tokio1), NoTls)
+            .map_err(|e| Error::from(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))?;
+        Ok(Self { pool })
+    }
+
+    /// Checks out a pooled client, awaiting a free slot if the pool is saturated.
+    async fn get(&self) -> Result<deadpool_postgres::Client, Error> {
+        self.pool
+            .get()
+            .await
+            .map_err(|e| Error::from(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+    }
+}
+
+/// Maps a `tokio_postgres::Row` into a typed struct, so reads go through
+/// `query_as` instead of ad-hoc positional `row.get(i)` calls.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, Error>;
+}
+
+/// A core KB table row, as inserted by `add_node`.
+#[derive(Debug, Clone)]
+pub struct NodeRow {
+    pub knowledge_base: String,
+    pub label: String,
+    pub name: String,
+    pub properties: Value,
+    pub data: Value,
+    pub has_link: bool,
+    pub path: String,
+}
+
+impl FromRow for NodeRow {
+    fn from_row(row: &Row) -> Result<Self, Error> {
+        Ok(Self {
+            knowledge_base: row.try_get(0)?,
+            label: row.try_get(1)?,
+            name: row.try_get(2)?,
+            properties: row.try_get::<_, Json<Value>>(3)?.0,
+            data: row.try_get::<_, Json<Value>>(4)?.0,
+            has_link: row.try_get(5)?,
+            path: row.try_get(6)?,
+        })
+    }
+}
+
 /// Manages a Postgres‐ltree KB schema and basic CRUD for KBs, nodes, links, and mounts.
 pub struct KnowledgeBaseManager {
-    client: Client,
+    conn: Conn,
     table_name: String,
 }
 
@@ -27,14 +117,28 @@ impl KnowledgeBaseManager {
         client.batch_execute("CREATE EXTENSION IF NOT EXISTS ltree;").await?;
 
         Ok(Self {
-            client,
+            conn: Conn::Owned(client),
+            table_name: table_name.to_string(),
+        })
+    }
+
+    /// Acquires a pooled client from `pool` instead of opening a dedicated
+    /// connection, so many managers can share one pool's max size and
+    /// recycling policy. Ensures the ltree extension exists on the pooled
+    /// connection just like `new` does on its owned one.
+    pub async fn from_pool(pool: &KbPool, table_name: &str) -> Result<Self, Error> {
+        let client = pool.get().await?;
+        client.batch_execute("CREATE EXTENSION IF NOT EXISTS ltree;").await?;
+
+        Ok(Self {
+            conn: Conn::Pooled(client),
             table_name: table_name.to_string(),
         })
     }
 
     /// Expose a mutable reference to the underlying client.
     pub fn client_mut(&mut self) -> &mut Client {
-        &mut self.client
+        self.conn.get_mut()
     }
 
     /// Expose the configured table name
@@ -44,13 +148,13 @@ impl KnowledgeBaseManager {
 
     /// Execute arbitrary SQL batch commands
     pub async fn batch_execute(&mut self, sql: &str) -> Result<(), Error> {
-        self.client.batch_execute(sql).await
+        self.conn.get_mut().batch_execute(sql).await
     }
 
     /// Drop a specific table in public schema
     pub async fn drop_table(&mut self, name: &str) -> Result<(), Error> {
         let stmt = format!("DROP TABLE IF EXISTS public.\"{}\" CASCADE;", name);
-        self.client.batch_execute(&stmt).await
+        self.conn.get_mut().batch_execute(&stmt).await
     }
 
     /// Insert into `<table_name>_info`
@@ -61,7 +165,7 @@ impl KnowledgeBaseManager {
             "INSERT INTO \"{}\" (knowledge_base, description) VALUES ($1, $2) ON CONFLICT (knowledge_base) DO NOTHING;",
             info_table
         );
-        self.client.execute(&stmt, &[&kb_name, &desc]).await
+        self.conn.get_mut().execute(&stmt, &[&kb_name, &desc]).await
     }
 
     /// Insert a node into `<table_name>`
@@ -80,7 +184,7 @@ impl KnowledgeBaseManager {
             "SELECT 1 FROM \"{}\" WHERE knowledge_base = $1;",
             info_table
         );
-        if self.client.query_opt(&check, &[&kb_name]).await?.is_none() {
+        if self.conn.get().query_opt(&check, &[&kb_name]).await?.is_none() {
             return Err(Error::from(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 format!("KB '{}' not found", kb_name),
@@ -95,11 +199,34 @@ impl KnowledgeBaseManager {
         );
         let props = properties.map(|v| Json(v.clone()));
         let data = data.map(|v| Json(v.clone()));
-        self.client
+        self.conn
+            .get_mut()
             .execute(&stmt, &[&kb_name, &label, &name, &props, &data, &path])
             .await
     }
 
+    /// Runs `sql` and maps every returned row into `T`, so reads get a
+    /// typed `Result` per row instead of positional `row.get(i)` calls.
+    pub async fn query_as<T: FromRow>(
+        &mut self,
+        sql: &str,
+        params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<Vec<T>, Error> {
+        let rows = self.conn.get_mut().query(sql, params).await?;
+        rows.iter().map(T::from_row).collect()
+    }
+
+    /// Looks up the node stored at `path`, if any.
+    pub async fn get_node_by_path(&mut self, path: &str) -> Result<Option<NodeRow>, Error> {
+        let stmt = format!(
+            "SELECT knowledge_base, label, name, properties, data, has_link, path::text \
+             FROM \"{}\" WHERE path = $1;",
+            self.table_name
+        );
+        let rows: Vec<NodeRow> = self.query_as(&stmt, &[&path]).await?;
+        Ok(rows.into_iter().next())
+    }
+
     /// Add a link in `<table_name>_link` and flip the `has_link` flag.
     pub async fn add_link(
         &mut self,
@@ -112,13 +239,16 @@ impl KnowledgeBaseManager {
             "INSERT INTO \"{}\" (parent_node_kb, parent_path, link_name) VALUES ($1, $2, $3);",
             link_tbl
         );
-        self.client.execute(&stmt, &[&parent_kb, &parent_path, &link_name]).await?;
+        self.conn
+            .get_mut()
+            .execute(&stmt, &[&parent_kb, &parent_path, &link_name])
+            .await?;
 
         let upd = format!(
             "UPDATE \"{}\" SET has_link = TRUE WHERE path = $1;",
             self.table_name
         );
-        self.client.execute(&upd, &[&parent_path]).await
+        self.conn.get_mut().execute(&upd, &[&parent_path]).await
     }
 
     /// Add a link‐mount under the current header path.
@@ -134,18 +264,20 @@ impl KnowledgeBaseManager {
             "INSERT INTO \"{}\" (link_name, knowledge_base, mount_path, description) VALUES ($1, $2, $3, $4);",
             mount_tbl
         );
-        self.client.execute(&stmt, &[&link_mount_name, &kb, &path, &description]).await?;
+        self.conn
+            .get_mut()
+            .execute(&stmt, &[&link_mount_name, &kb, &path, &description])
+            .await?;
 
         let upd = format!(
             "UPDATE \"{}\" SET has_link_mount = TRUE WHERE knowledge_base = $1 AND path = $2;",
             self.table_name
         );
-        self.client.execute(&upd, &[&kb, &path]).await
+        self.conn.get_mut().execute(&upd, &[&kb, &path]).await
     }
 
     /// Close the connection (dropping finalizes it).
     pub async fn disconnect(self) {
-        // Client drops here
+        // Conn drops here
     }
 }
-```
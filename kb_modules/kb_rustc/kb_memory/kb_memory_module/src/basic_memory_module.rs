@@ -1,10 +1,21 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use lru::LruCache;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::{PgPool, Row};
+use sqlx::{PgPool, Row, SqlitePool};
 use std::error::Error;
 use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::storage_backend::StorageBackend;
+
+/// Number of compiled lquery programs kept hot, mirroring the bound
+/// upend uses for its hierarchy lookup cache.
+const MATCHER_CACHE_CAPACITY: usize = 256;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TreeNode {
@@ -12,6 +23,7 @@ pub struct TreeNode {
     pub data: Value,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
+    pub version: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +47,33 @@ pub struct TreeStats {
 pub struct SyncStats {
     pub imported: usize,
     pub exported: usize,
+    /// Nodes left untouched because they weren't dirty since the last sync.
+    pub skipped: usize,
+    /// Tombstoned paths whose rows were deleted from the remote table.
+    pub deleted: usize,
+}
+
+/// A token in an ltxtquery string: a (possibly prefix) word, a boolean
+/// operator, or a parenthesis.
+#[derive(Debug, Clone, PartialEq)]
+enum LtxtToken {
+    Word(String, bool),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+/// Parsed boolean expression tree for an ltxtquery string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LtxtExpr {
+    /// `Word(w, false)` matches a label equal to `w`; `Word(w, true)` (written
+    /// `w*` in the query) matches a label that starts with `w`.
+    Word(String, bool),
+    And(Box<LtxtExpr>, Box<LtxtExpr>),
+    Or(Box<LtxtExpr>, Box<LtxtExpr>),
+    Not(Box<LtxtExpr>),
 }
 
 #[derive(Debug)]
@@ -44,6 +83,8 @@ pub enum KbError {
     KnowledgeBaseExists(String),
     PathNotFound(String),
     ValidationError(String),
+    VersionConflict { path: String, expected: u64, actual: u64 },
+    RegexCompileError(String, String),
 }
 
 impl fmt::Display for KbError {
@@ -54,12 +95,41 @@ impl fmt::Display for KbError {
             KbError::KnowledgeBaseExists(name) => write!(f, "Knowledge base {} already exists", name),
             KbError::PathNotFound(path) => write!(f, "Path {} does not exist", path),
             KbError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
+            KbError::VersionConflict { path, expected, actual } => write!(
+                f,
+                "Version conflict at {}: expected {}, found {}",
+                path, expected, actual
+            ),
+            KbError::RegexCompileError(query, msg) => write!(f, "Failed to compile pattern '{}': {}", query, msg),
         }
     }
 }
 
 impl Error for KbError {}
 
+/// A secondary-index key: a JSON-pointer field path paired with the
+/// canonical (JSON-encoded) value found there. Strings are used instead of
+/// `serde_json::Value` directly since `Value` has no `Hash` impl.
+type IndexKey = (String, String);
+
+/// One alternative within a compiled lquery label level, e.g. the `foo*` in
+/// `foo*|bar`.
+struct LabelPattern {
+    text: String,
+    /// Whether `text` matches as a prefix (`foo@`/`foo*`) rather than exact equality.
+    prefix: bool,
+}
+
+/// One compiled level of an lquery pattern, as produced by `compile_lquery`.
+enum LquerySegment {
+    /// Matches exactly one label against a set of alternatives (`foo|bar*`),
+    /// optionally negated (`!foo`).
+    Label { alternatives: Vec<LabelPattern>, negate: bool },
+    /// Matches between `min` and `max` (`None` = unbounded) labels, covering
+    /// `*` (`{1,1}`), `**` (`{0,}`), and `*{n}`/`*{n,m}`/`*{n,}`/`*{,m}`.
+    Star { min: usize, max: Option<usize> },
+}
+
 pub struct BasicConstructDB {
     data: HashMap<String, TreeNode>,
     kb_dict: HashMap<String, HashMap<String, Value>>,
@@ -70,6 +140,27 @@ pub struct BasicConstructDB {
     password: String,
     table_name: String,
     //connection_params: HashMap<String, Value>,
+    /// Compiled lquery programs (one `LquerySegment` per path level), keyed
+    /// by the raw query string so repeated `query()` calls over the same
+    /// pattern don't recompile it once per stored node.
+    matcher_cache: RefCell<LruCache<String, Arc<Vec<LquerySegment>>>>,
+    /// Optional pluggable persistence backend (e.g. an embedded `SledBackend`)
+    /// that `sync_to_backend`/`load_from_backend` mirror the in-memory tree
+    /// against, the same way `sync_with_postgres` mirrors against Postgres.
+    backend: Option<Box<dyn StorageBackend>>,
+    /// Paths stored or modified since the last `export_to_postgres_incremental`.
+    dirty: HashSet<String>,
+    /// Paths deleted since the last `export_to_postgres_incremental`, so the
+    /// matching remote row can be deleted instead of left stale.
+    tombstones: HashSet<String>,
+    /// The newest `updated_at` seen by `import_from_postgres_incremental`, used
+    /// as the watermark for the next incremental import.
+    last_synced_at: Option<String>,
+    /// JSON-pointer field paths registered via `create_index`.
+    indexed_fields: HashSet<String>,
+    /// (field, value) -> paths whose `data` has that value at `field`, for
+    /// every field in `indexed_fields`.
+    indexes: HashMap<IndexKey, HashSet<String>>,
 }
 
 impl BasicConstructDB {
@@ -98,7 +189,141 @@ impl BasicConstructDB {
             password,
             table_name,
             //connection_params,
+            matcher_cache: RefCell::new(LruCache::new(NonZeroUsize::new(MATCHER_CACHE_CAPACITY).unwrap())),
+            backend: None,
+            dirty: HashSet::new(),
+            tombstones: HashSet::new(),
+            last_synced_at: None,
+            indexed_fields: HashSet::new(),
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// Canonicalizes a JSON value into the string half of an `IndexKey`.
+    fn index_value_key(value: &Value) -> String {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+
+    /// Registers a JSON-pointer field path (e.g. `/type`) as indexed and
+    /// backfills the index from every node already in the tree. Indexing the
+    /// same field twice is a no-op.
+    pub fn create_index(&mut self, field_path: &str) {
+        if !self.indexed_fields.insert(field_path.to_string()) {
+            return;
+        }
+        for (path, node) in &self.data {
+            if let Some(value) = node.data.pointer(field_path) {
+                let key = (field_path.to_string(), Self::index_value_key(value));
+                self.indexes.entry(key).or_default().insert(path.clone());
+            }
+        }
+    }
+
+    /// Removes `path` from every indexed field's entry for `data`, the
+    /// inverse of what `update_indexes_on_store` adds.
+    fn remove_indexes_for(&mut self, path: &str, data: &Value) {
+        for field in self.indexed_fields.clone() {
+            if let Some(value) = data.pointer(&field) {
+                let key = (field, Self::index_value_key(value));
+                if let Some(paths) = self.indexes.get_mut(&key) {
+                    paths.remove(path);
+                    if paths.is_empty() {
+                        self.indexes.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves `path`'s index entries from `old_data` (if it previously
+    /// existed) to `new_data`, for every indexed field.
+    fn update_indexes_on_store(&mut self, path: &str, old_data: Option<&Value>, new_data: &Value) {
+        if let Some(old_data) = old_data {
+            self.remove_indexes_for(path, old_data);
+        }
+        for field in self.indexed_fields.clone() {
+            if let Some(value) = new_data.pointer(&field) {
+                let key = (field, Self::index_value_key(value));
+                self.indexes.entry(key).or_default().insert(path.to_string());
+            }
+        }
+    }
+
+    /// Finds every node whose `data` has `value` at the JSON-pointer `field`.
+    /// Uses the secondary index when `field` is indexed; otherwise falls
+    /// back to a full scan.
+    pub fn query_where(&self, field: &str, value: &Value) -> Vec<QueryResult> {
+        let to_result = |path: &String, node: &TreeNode| QueryResult {
+            path: path.clone(),
+            data: node.data.clone(),
+            created_at: node.created_at.clone(),
+            updated_at: node.updated_at.clone(),
+        };
+
+        if self.indexed_fields.contains(field) {
+            let key = (field.to_string(), Self::index_value_key(value));
+            self.indexes
+                .get(&key)
+                .into_iter()
+                .flatten()
+                .filter_map(|path| self.data.get(path).map(|node| to_result(path, node)))
+                .collect()
+        } else {
+            self.data
+                .iter()
+                .filter(|(_, node)| node.data.pointer(field) == Some(value))
+                .map(|(path, node)| to_result(path, node))
+                .collect()
+        }
+    }
+
+    /// Starts a [`QueryBuilder`] that can combine a path pattern with
+    /// `query_where`-style equality predicates.
+    pub fn query_builder(&self) -> QueryBuilder<'_> {
+        QueryBuilder {
+            db: self,
+            pattern: None,
+            predicates: Vec::new(),
+        }
+    }
+
+    /// Attaches a pluggable storage backend that `sync_to_backend`/
+    /// `load_from_backend` mirror this tree against, so it can be built up
+    /// fully offline (e.g. against a `SledBackend`) and synced to Postgres
+    /// later instead of hitting a live database on every write.
+    pub fn set_backend(&mut self, mut backend: Box<dyn StorageBackend>) -> Result<(), KbError> {
+        backend.open_tree(&self.table_name)?;
+        self.backend = Some(backend);
+        Ok(())
+    }
+
+    /// Pushes every in-memory node into the attached backend.
+    pub fn sync_to_backend(&mut self) -> Result<usize, KbError> {
+        let backend = self
+            .backend
+            .as_mut()
+            .ok_or_else(|| KbError::DatabaseError("No storage backend attached".to_string()))?;
+        for node in self.data.values() {
+            backend.insert(&node.path, node)?;
+        }
+        Ok(self.data.len())
+    }
+
+    /// Loads every node from the attached backend into memory, overwriting
+    /// whichever paths it defines.
+    pub fn load_from_backend(&mut self) -> Result<usize, KbError> {
+        let nodes = {
+            let backend = self
+                .backend
+                .as_ref()
+                .ok_or_else(|| KbError::DatabaseError("No storage backend attached".to_string()))?;
+            backend.iter()?
+        };
+        let count = nodes.len();
+        for node in nodes {
+            self.data.insert(node.path.clone(), node);
         }
+        Ok(count)
     }
 
     pub fn add_kb(&mut self, kb_name: &str, description: &str) -> Result<(), KbError> {
@@ -230,46 +455,294 @@ impl BasicConstructDB {
         format!("^{}$", result)
     }
 
-    pub fn ltree_match(&self, path: &str, query: &str) -> bool {
-        let regex_pattern = self.convert_ltree_query_to_regex(query);
-        Regex::new(&regex_pattern).map(|re| re.is_match(path)).unwrap_or(false)
+    /// Returns the compiled lquery program for `query`, compiling and caching
+    /// it on a miss.
+    fn compiled_lquery(&self, query: &str) -> Result<Arc<Vec<LquerySegment>>, KbError> {
+        if let Some(program) = self.matcher_cache.borrow_mut().get(query) {
+            return Ok(program.clone());
+        }
+
+        let program = Arc::new(Self::compile_lquery(query)?);
+        self.matcher_cache.borrow_mut().put(query.to_string(), program.clone());
+        Ok(program)
+    }
+
+    /// Parses a single `n`, `n,m`, `n,`, or `,m` quantifier body (the part
+    /// between `*{` and `}`) into an inclusive `(min, max)` range.
+    fn parse_lquery_quantifier(query: &str, inner: &str) -> Result<(usize, Option<usize>), KbError> {
+        let invalid = || KbError::RegexCompileError(query.to_string(), format!("invalid quantifier '*{{{}}}'", inner));
+
+        if let Some((lo, hi)) = inner.split_once(',') {
+            let min: usize = if lo.is_empty() { 0 } else { lo.parse().map_err(|_| invalid())? };
+            if hi.is_empty() {
+                Ok((min, None))
+            } else {
+                let max: usize = hi.parse().map_err(|_| invalid())?;
+                if max < min {
+                    return Err(invalid());
+                }
+                Ok((min, Some(max)))
+            }
+        } else {
+            let n: usize = inner.parse().map_err(|_| invalid())?;
+            Ok((n, Some(n)))
+        }
     }
 
-    pub fn ltxtquery_match(&self, path: &str, ltxtquery: &str) -> bool {
-        let mut path_words = HashMap::new();
-        for word in path.split('.') {
-            path_words.insert(word, true);
+    /// Compiles an lquery string into a sequence of per-level matchers: a
+    /// plain label becomes a single-label `Label` alternative, `foo|bar`
+    /// becomes multiple alternatives, a `!` prefix negates the whole level,
+    /// a `foo@`/`foo*` suffix makes an alternative match as a prefix, and
+    /// `*`/`**`/`*{n}`/`*{n,m}`/`*{n,}`/`*{,m}` become `Star` ranges.
+    fn compile_lquery(query: &str) -> Result<Vec<LquerySegment>, KbError> {
+        let mut segments = Vec::new();
+
+        for token in query.split('.') {
+            if token.is_empty() {
+                return Err(KbError::RegexCompileError(query.to_string(), "empty path level".to_string()));
+            } else if token == "*" {
+                segments.push(LquerySegment::Star { min: 1, max: Some(1) });
+            } else if token == "**" {
+                segments.push(LquerySegment::Star { min: 0, max: None });
+            } else if let Some(inner) = token.strip_prefix("*{").and_then(|rest| rest.strip_suffix('}')) {
+                let (min, max) = Self::parse_lquery_quantifier(query, inner)?;
+                segments.push(LquerySegment::Star { min, max });
+            } else {
+                let negate = token.starts_with('!');
+                let body = if negate { &token[1..] } else { token };
+                if body.is_empty() {
+                    return Err(KbError::RegexCompileError(query.to_string(), "negated level missing a label".to_string()));
+                }
+
+                let alternatives = body
+                    .split('|')
+                    .map(|alt| match alt.strip_suffix('@').or_else(|| alt.strip_suffix('*')) {
+                        Some(stripped) => LabelPattern { text: stripped.to_string(), prefix: true },
+                        None => LabelPattern { text: alt.to_string(), prefix: false },
+                    })
+                    .collect();
+
+                segments.push(LquerySegment::Label { alternatives, negate });
+            }
         }
 
-        let query = ltxtquery.trim();
+        Ok(segments)
+    }
+
+    /// Runs a position-set simulation of `segments` over `labels`: `reachable`
+    /// holds every label index still consistent with the levels consumed so
+    /// far, single-label levels advance each position by one on a match, and
+    /// `Star` levels expand each position by every length in `min..=max`. The
+    /// whole path matches iff `labels.len()` is reachable once every level has
+    /// been consumed.
+    fn run_lquery(segments: &[LquerySegment], labels: &[&str]) -> bool {
+        let mut reachable: HashSet<usize> = HashSet::from([0]);
+
+        for segment in segments {
+            let mut next = HashSet::new();
+            match segment {
+                LquerySegment::Label { alternatives, negate } => {
+                    for &pos in &reachable {
+                        if pos >= labels.len() {
+                            continue;
+                        }
+                        let label = labels[pos];
+                        let hit = alternatives.iter().any(|alt| {
+                            if alt.prefix {
+                                label.starts_with(&alt.text)
+                            } else {
+                                label == alt.text
+                            }
+                        });
+                        if hit != *negate {
+                            next.insert(pos + 1);
+                        }
+                    }
+                }
+                LquerySegment::Star { min, max } => {
+                    for &pos in &reachable {
+                        let upper = max.map(|m| pos + m).unwrap_or(labels.len()).min(labels.len());
+                        let lower = pos + min;
+                        if lower <= upper {
+                            next.extend(lower..=upper);
+                        }
+                    }
+                }
+            }
 
-        // Handle simple cases first
-        if !query.contains('&') && !query.contains('|') && !query.contains('!') {
-            return path_words.contains_key(query.trim());
+            if next.is_empty() {
+                return false;
+            }
+            reachable = next;
         }
 
-        // This is a simplified implementation for basic boolean operations
-        if query.contains('&') {
-            let words: Vec<&str> = query.split('&').collect();
-            for word in words {
-                if !path_words.contains_key(word.trim()) {
-                    return false;
+        reachable.contains(&labels.len())
+    }
+
+    /// Matches `path` against an lquery pattern, supporting per-label
+    /// wildcards with quantifiers (`*{n}`, `*{n,m}`, `*{n,}`), alternation
+    /// (`foo|bar`), prefix matching (`foo@`/`foo*`), and negation (`!foo`), in
+    /// addition to the plain `*`/`**` wildcards.
+    pub fn ltree_match(&self, path: &str, query: &str) -> Result<bool, KbError> {
+        let program = self.compiled_lquery(query)?;
+        let labels: Vec<&str> = path.split('.').collect();
+        Ok(Self::run_lquery(&program, &labels))
+    }
+
+    pub fn ltxtquery_match(&self, path: &str, ltxtquery: &str) -> Result<bool, KbError> {
+        let expr = self.parse_ltxtquery(ltxtquery)?;
+        let labels: Vec<&str> = path.split('.').collect();
+        Ok(Self::eval_ltxtquery(&expr, &labels))
+    }
+
+    /// Tokenizes an ltxtquery string into words (optionally `word*` prefixes),
+    /// `&`/`|`/`!` operators, and parentheses.
+    fn tokenize_ltxtquery(&self, query: &str) -> Result<Vec<LtxtToken>, KbError> {
+        let mut tokens = Vec::new();
+        let mut chars = query.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            match c {
+                c if c.is_whitespace() => {
+                    chars.next();
+                }
+                '&' => {
+                    chars.next();
+                    tokens.push(LtxtToken::And);
+                }
+                '|' => {
+                    chars.next();
+                    tokens.push(LtxtToken::Or);
+                }
+                '!' => {
+                    chars.next();
+                    tokens.push(LtxtToken::Not);
+                }
+                '(' => {
+                    chars.next();
+                    tokens.push(LtxtToken::LParen);
+                }
+                ')' => {
+                    chars.next();
+                    tokens.push(LtxtToken::RParen);
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || "&|!()".contains(c) {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    if word.is_empty() {
+                        return Err(KbError::ValidationError(format!(
+                            "Unexpected character '{}' in ltxtquery '{}'",
+                            c, query
+                        )));
+                    }
+                    let prefix = word.ends_with('*');
+                    if prefix {
+                        word.pop();
+                    }
+                    if word.is_empty() {
+                        return Err(KbError::ValidationError(format!(
+                            "Empty word in ltxtquery '{}'",
+                            query
+                        )));
+                    }
+                    tokens.push(LtxtToken::Word(word, prefix));
                 }
             }
-            return true;
         }
 
-        if query.contains('|') {
-            let words: Vec<&str> = query.split('|').collect();
-            for word in words {
-                if path_words.contains_key(word.trim()) {
-                    return true;
+        Ok(tokens)
+    }
+
+    /// Parses an ltxtquery string into a boolean expression tree, mirroring the
+    /// precedence of PostgreSQL's `ltxtquery` operator: `!` binds tightest, then
+    /// `&`, then `|`, with parentheses overriding either.
+    pub fn parse_ltxtquery(&self, query: &str) -> Result<LtxtExpr, KbError> {
+        let tokens = self.tokenize_ltxtquery(query)?;
+        if tokens.is_empty() {
+            return Err(KbError::ValidationError(format!("Empty ltxtquery '{}'", query)));
+        }
+
+        let mut pos = 0;
+        let expr = Self::parse_or(&tokens, &mut pos, query)?;
+        if pos != tokens.len() {
+            return Err(KbError::ValidationError(format!(
+                "Unexpected trailing input in ltxtquery '{}'",
+                query
+            )));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(tokens: &[LtxtToken], pos: &mut usize, query: &str) -> Result<LtxtExpr, KbError> {
+        let mut expr = Self::parse_and(tokens, pos, query)?;
+        while matches!(tokens.get(*pos), Some(LtxtToken::Or)) {
+            *pos += 1;
+            let rhs = Self::parse_and(tokens, pos, query)?;
+            expr = LtxtExpr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(tokens: &[LtxtToken], pos: &mut usize, query: &str) -> Result<LtxtExpr, KbError> {
+        let mut expr = Self::parse_not(tokens, pos, query)?;
+        while matches!(tokens.get(*pos), Some(LtxtToken::And)) {
+            *pos += 1;
+            let rhs = Self::parse_not(tokens, pos, query)?;
+            expr = LtxtExpr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(tokens: &[LtxtToken], pos: &mut usize, query: &str) -> Result<LtxtExpr, KbError> {
+        if matches!(tokens.get(*pos), Some(LtxtToken::Not)) {
+            *pos += 1;
+            let inner = Self::parse_not(tokens, pos, query)?;
+            return Ok(LtxtExpr::Not(Box::new(inner)));
+        }
+        Self::parse_primary(tokens, pos, query)
+    }
+
+    fn parse_primary(tokens: &[LtxtToken], pos: &mut usize, query: &str) -> Result<LtxtExpr, KbError> {
+        match tokens.get(*pos) {
+            Some(LtxtToken::Word(word, prefix)) => {
+                *pos += 1;
+                Ok(LtxtExpr::Word(word.clone(), *prefix))
+            }
+            Some(LtxtToken::LParen) => {
+                *pos += 1;
+                let expr = Self::parse_or(tokens, pos, query)?;
+                match tokens.get(*pos) {
+                    Some(LtxtToken::RParen) => {
+                        *pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(KbError::ValidationError(format!(
+                        "Missing closing parenthesis in ltxtquery '{}'",
+                        query
+                    ))),
                 }
             }
-            return false;
+            _ => Err(KbError::ValidationError(format!(
+                "Expected a word or '(' in ltxtquery '{}'",
+                query
+            ))),
         }
+    }
 
-        false
+    fn eval_ltxtquery(expr: &LtxtExpr, labels: &[&str]) -> bool {
+        match expr {
+            LtxtExpr::Word(word, false) => labels.iter().any(|label| *label == word),
+            LtxtExpr::Word(word, true) => labels.iter().any(|label| label.starts_with(word.as_str())),
+            LtxtExpr::And(lhs, rhs) => Self::eval_ltxtquery(lhs, labels) && Self::eval_ltxtquery(rhs, labels),
+            LtxtExpr::Or(lhs, rhs) => Self::eval_ltxtquery(lhs, labels) || Self::eval_ltxtquery(rhs, labels),
+            LtxtExpr::Not(inner) => !Self::eval_ltxtquery(inner, labels),
+        }
     }
 
     pub fn ltree_ancestor(&self, ancestor: &str, descendant: &str) -> bool {
@@ -387,20 +860,78 @@ impl BasicConstructDB {
         }
     }
 
-    pub fn store(&mut self, path: &str, data: Value, created_at: Option<String>, updated_at: Option<String>) -> Result<(), KbError> {
+    pub fn store(&mut self, path: &str, data: Value, created_at: Option<String>, updated_at: Option<String>) -> Result<u64, KbError> {
         if !self.validate_path(path) {
             return Err(KbError::InvalidPath(path.to_string()));
         }
 
+        let existing = self.data.get(path);
+        let version = existing.map(|existing| existing.version + 1).unwrap_or(1);
+        let old_data = existing.map(|existing| existing.data.clone());
+        self.update_indexes_on_store(path, old_data.as_ref(), &data);
+
         self.data.insert(path.to_string(), TreeNode {
             path: path.to_string(),
             data,
             created_at,
             updated_at,
+            version,
         });
+        self.dirty.insert(path.to_string());
+        self.tombstones.remove(path);
+        Ok(version)
+    }
+
+    /// Re-inserts `node` exactly as given, preserving its `version` instead of
+    /// bumping it the way `store` does — used to restore a node to a prior
+    /// snapshot (transaction rollback, binary snapshot reload) without
+    /// disturbing the CAS version sequence that `compare_and_store` relies on.
+    pub fn restore_node(&mut self, node: TreeNode) -> Result<(), KbError> {
+        if !self.validate_path(&node.path) {
+            return Err(KbError::InvalidPath(node.path));
+        }
+
+        let old_data = self.data.get(&node.path).map(|existing| existing.data.clone());
+        self.update_indexes_on_store(&node.path, old_data.as_ref(), &node.data);
+        self.dirty.insert(node.path.clone());
+        self.tombstones.remove(&node.path);
+        self.data.insert(node.path.clone(), node);
         Ok(())
     }
 
+    /// Overwrites `path` only if its current version matches `expected_version`,
+    /// mirroring the CAS guard used by versioned tree stores like vertree.
+    pub fn compare_and_store(
+        &mut self,
+        path: &str,
+        data: Value,
+        expected_version: u64,
+    ) -> Result<u64, KbError> {
+        if !self.validate_path(path) {
+            return Err(KbError::InvalidPath(path.to_string()));
+        }
+
+        let (created_at, updated_at, actual_version) = match self.data.get(path) {
+            Some(existing) => (existing.created_at.clone(), existing.updated_at.clone(), existing.version),
+            None => (None, None, 0),
+        };
+
+        if actual_version != expected_version {
+            return Err(KbError::VersionConflict {
+                path: path.to_string(),
+                expected: expected_version,
+                actual: actual_version,
+            });
+        }
+
+        self.store(path, data, created_at, updated_at)
+    }
+
+    /// Reads a node together with its current version, for read-then-CAS callers.
+    pub fn get_versioned(&self, path: &str) -> Option<(Value, u64)> {
+        self.data.get(path).map(|node| (node.data.clone(), node.version))
+    }
+
     pub fn get(&self, path: &str) -> Result<Option<Value>, KbError> {
         if !self.validate_path(path) {
             return Err(KbError::InvalidPath(path.to_string()));
@@ -417,11 +948,11 @@ impl BasicConstructDB {
         Ok(self.data.get(path).cloned())
     }
 
-    pub fn query(&self, pattern: &str) -> Vec<QueryResult> {
+    pub fn query(&self, pattern: &str) -> Result<Vec<QueryResult>, KbError> {
         let mut results = Vec::new();
 
         for (path, node) in &self.data {
-            if self.ltree_match(path, pattern) {
+            if self.ltree_match(path, pattern)? {
                 results.push(QueryResult {
                     path: path.clone(),
                     data: node.data.clone(),
@@ -432,14 +963,16 @@ impl BasicConstructDB {
         }
 
         results.sort_by(|a, b| a.path.cmp(&b.path));
-        results
+        Ok(results)
     }
 
-    pub fn query_ltxtquery(&self, ltxtquery: &str) -> Vec<QueryResult> {
+    pub fn query_ltxtquery(&self, ltxtquery: &str) -> Result<Vec<QueryResult>, KbError> {
+        let expr = self.parse_ltxtquery(ltxtquery)?;
         let mut results = Vec::new();
 
         for (path, node) in &self.data {
-            if self.ltxtquery_match(path, ltxtquery) {
+            let labels: Vec<&str> = path.split('.').collect();
+            if Self::eval_ltxtquery(&expr, &labels) {
                 results.push(QueryResult {
                     path: path.clone(),
                     data: node.data.clone(),
@@ -450,10 +983,10 @@ impl BasicConstructDB {
         }
 
         results.sort_by(|a, b| a.path.cmp(&b.path));
-        results
+        Ok(results)
     }
 
-    pub fn query_by_operator(&self, operator: &str, path1: &str, _path2: &str) -> Vec<QueryResult> {
+    pub fn query_by_operator(&self, operator: &str, path1: &str, _path2: &str) -> Result<Vec<QueryResult>, KbError> {
         let mut results = Vec::new();
 
         match operator {
@@ -489,7 +1022,7 @@ impl BasicConstructDB {
         }
 
         results.sort_by(|a, b| a.path.cmp(&b.path));
-        results
+        Ok(results)
     }
 
     pub fn query_ancestors(&self, path: &str) -> Result<Vec<QueryResult>, KbError> {
@@ -567,7 +1100,16 @@ impl BasicConstructDB {
     }
 
     pub fn delete(&mut self, path: &str) -> bool {
-        self.data.remove(path).is_some()
+        if let Some(node) = self.data.get(path) {
+            let data = node.data.clone();
+            self.remove_indexes_for(path, &data);
+        }
+        let removed = self.data.remove(path).is_some();
+        if removed {
+            self.dirty.remove(path);
+            self.tombstones.insert(path.to_string());
+        }
+        removed
     }
 
     pub fn add_subtree(&mut self, path: &str, subtree: &[QueryResult]) -> Result<(), KbError> {
@@ -602,7 +1144,13 @@ impl BasicConstructDB {
         // Delete them
         let count = to_delete.len();
         for delete_path in to_delete {
+            if let Some(node) = self.data.get(&delete_path) {
+                let data = node.data.clone();
+                self.remove_indexes_for(&delete_path, &data);
+            }
             self.data.remove(&delete_path);
+            self.dirty.remove(&delete_path);
+            self.tombstones.insert(delete_path);
         }
 
         count
@@ -755,102 +1303,724 @@ impl BasicConstructDB {
         Ok(exported_count)
     }
 
-    pub async fn sync_with_postgres(&mut self, direction: &str) -> SyncStats {
-        let mut stats = SyncStats {
-            imported: 0,
-            exported: 0,
-        };
+    /// Imports rows from a SQLite database into this tree. Unlike Postgres,
+    /// SQLite has no `ltree`/GiST support, so `path` is a plain unique `TEXT`
+    /// column and the ancestor/descendant filtering happens here via the
+    /// existing `ltree_match`/`ltree_descendant` helpers after loading rows.
+    pub async fn import_from_sqlite(
+        &mut self,
+        db_path: &str,
+        table_name: &str,
+    ) -> Result<usize, KbError> {
+        let pool = SqlitePool::connect(db_path)
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
 
-        if direction == "import" || direction == "both" {
-            if let Ok(imported) = self.import_from_postgres(
-                &self.table_name.clone(),
-                "path",
-                "data",
-                "created_at",
-                "updated_at",
-            ).await {
-                stats.imported = imported;
-            }
-        }
+        let exists_query = "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?";
+        let exists: Option<String> = sqlx::query_scalar(exists_query)
+            .bind(table_name)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
 
-        if direction == "export" || direction == "both" {
-            if let Ok(exported) = self.export_to_postgres(&self.table_name.clone(), true, false).await {
-                stats.exported = exported;
-            }
+        if exists.is_none() {
+            return Err(KbError::DatabaseError(format!("Table '{}' does not exist", table_name)));
         }
 
-        stats
-    }
+        let query = format!(
+            "SELECT path, data, created_at, updated_at FROM {} ORDER BY path",
+            table_name
+        );
+        let rows = sqlx::query(&query)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
 
-    pub fn get_stats(&self) -> TreeStats {
-        if self.data.is_empty() {
-            return TreeStats {
-                total_nodes: 0,
-                max_depth: 0,
-                avg_depth: 0.0,
-                root_nodes: 0,
-                leaf_nodes: 0,
-            };
-        }
+        let mut imported_count = 0;
+        for row in rows {
+            let path: String = row.get("path");
+            let data_text: Option<String> = row.try_get("data").ok();
+            let created_at: Option<String> = row.try_get("created_at").ok();
+            let updated_at: Option<String> = row.try_get("updated_at").ok();
 
-        let mut depths = Vec::new();
-        let mut root_nodes = 0;
+            let data = data_text
+                .and_then(|text| serde_json::from_str(&text).ok())
+                .unwrap_or(Value::Null);
 
-        for path in self.data.keys() {
-            let depth = self.nlevel(path);
-            depths.push(depth);
-            if depth == 1 {
-                root_nodes += 1;
+            if self.store(&path, data, created_at, updated_at).is_ok() {
+                imported_count += 1;
             }
         }
 
-        // Calculate max depth
-        let max_depth = depths.iter().max().copied().unwrap_or(0);
-        let total_depth: usize = depths.iter().sum();
+        pool.close().await;
+        Ok(imported_count)
+    }
 
-        // Count leaf nodes (nodes with no children)
-        let mut leaf_nodes = 0;
-        for path in self.data.keys() {
-            let mut has_children = false;
-            for other_path in self.data.keys() {
-                if self.ltree_ancestor(path, other_path) {
-                    has_children = true;
-                    break;
-                }
-            }
-            if !has_children {
-                leaf_nodes += 1;
-            }
-        }
+    /// Exports this tree to a SQLite database, storing `data` as JSON-encoded
+    /// `TEXT` and `path` under a plain unique index (no `ltree` type exists
+    /// in SQLite).
+    pub async fn export_to_sqlite(
+        &self,
+        db_path: &str,
+        table_name: &str,
+        create_table: bool,
+        clear_existing: bool,
+    ) -> Result<usize, KbError> {
+        let pool = SqlitePool::connect(db_path)
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
 
-        let avg_depth = if depths.is_empty() {
-            0.0
-        } else {
-            total_depth as f64 / depths.len() as f64
-        };
+        if create_table {
+            let create_table_query = format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    path TEXT UNIQUE NOT NULL,
+                    data TEXT,
+                    created_at TEXT,
+                    updated_at TEXT
+                )",
+                table_name
+            );
+            sqlx::query(&create_table_query)
+                .execute(&pool)
+                .await
+                .map_err(|e| KbError::DatabaseError(e.to_string()))?;
 
-        TreeStats {
-            total_nodes: self.data.len(),
-            max_depth,
-            avg_depth,
-            root_nodes,
-            leaf_nodes,
+            let path_index = format!("CREATE INDEX IF NOT EXISTS {}_path_idx ON {} (path)", table_name, table_name);
+            sqlx::query(&path_index)
+                .execute(&pool)
+                .await
+                .map_err(|e| KbError::DatabaseError(e.to_string()))?;
         }
-    }
 
-    pub fn clear(&mut self) {
-        self.data.clear();
-    }
+        if clear_existing {
+            let truncate_query = format!("DELETE FROM {}", table_name);
+            sqlx::query(&truncate_query)
+                .execute(&pool)
+                .await
+                .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        }
 
-    pub fn size(&self) -> usize {
-        self.data.len()
-    }
+        let mut exported_count = 0;
+        let insert_query = format!(
+            "INSERT INTO {} (path, data, created_at, updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT (path) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+            table_name
+        );
+
+        for (path, node) in &self.data {
+            let data_text = serde_json::to_string(&node.data).unwrap_or_else(|_| "null".to_string());
+
+            let result = sqlx::query(&insert_query)
+                .bind(path)
+                .bind(&data_text)
+                .bind(&node.created_at)
+                .bind(&node.updated_at)
+                .execute(&pool)
+                .await;
+
+            if result.is_ok() {
+                exported_count += 1;
+            }
+        }
+
+        pool.close().await;
+        Ok(exported_count)
+    }
+
+    /// Incremental counterpart to `import_from_postgres`: only pulls rows
+    /// whose `updated_at` is newer than `last_synced_at`, then advances the
+    /// watermark to the newest `updated_at` it saw. Freshly-imported paths
+    /// are dropped from `dirty` since they already match the remote row.
+    pub async fn import_from_postgres_incremental(&mut self, table_name: &str) -> Result<usize, KbError> {
+        let pool = PgPool::connect(&self.get_database_url())
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+
+        let query = format!(
+            "SELECT path::text as path, data, created_at::text as created_at, updated_at::text as updated_at \
+             FROM {} WHERE $1::timestamp IS NULL OR updated_at > $1::timestamp ORDER BY updated_at",
+            table_name
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(&self.last_synced_at)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+
+        let mut imported_count = 0;
+        for row in rows {
+            let path: String = row.get("path");
+            let data_bytes: Option<Vec<u8>> = row.try_get("data").ok();
+            let created_at: Option<String> = row.try_get("created_at").ok();
+            let updated_at: Option<String> = row.try_get("updated_at").ok();
+
+            let data = if let Some(bytes) = data_bytes {
+                serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+            } else {
+                Value::Null
+            };
+
+            if self.store(&path, data, created_at, updated_at.clone()).is_ok() {
+                imported_count += 1;
+                self.dirty.remove(&path);
+                if updated_at.is_some() && updated_at > self.last_synced_at {
+                    self.last_synced_at = updated_at;
+                }
+            }
+        }
+
+        pool.close().await;
+        Ok(imported_count)
+    }
+
+    /// Incremental counterpart to `export_to_postgres`: only upserts paths in
+    /// `dirty` and issues deletes for `tombstones`, instead of rewriting the
+    /// whole table on every sync. Only the paths that actually succeeded are
+    /// cleared from each set — a path whose write fails (lock timeout,
+    /// dropped connection) stays dirty/tombstoned so the next sync retries
+    /// it, instead of being silently dropped from tracking forever.
+    pub async fn export_to_postgres_incremental(&mut self, table_name: &str) -> Result<(usize, usize), KbError> {
+        let pool = PgPool::connect(&self.get_database_url())
+            .await
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+
+        let insert_query = format!(
+            "INSERT INTO {} (path, data, created_at, updated_at) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (path) DO UPDATE SET data = EXCLUDED.data, updated_at = EXCLUDED.updated_at",
+            table_name
+        );
+
+        let mut exported_count = 0;
+        let mut exported_paths = Vec::new();
+        for path in &self.dirty {
+            if let Some(node) = self.data.get(path) {
+                let data_json = serde_json::to_value(&node.data).unwrap_or(Value::Null);
+                let result = sqlx::query(&insert_query)
+                    .bind(path)
+                    .bind(&data_json)
+                    .bind(&node.created_at)
+                    .bind(&node.updated_at)
+                    .execute(&pool)
+                    .await;
+
+                if result.is_ok() {
+                    exported_count += 1;
+                    exported_paths.push(path.clone());
+                }
+            }
+        }
+
+        let delete_query = format!("DELETE FROM {} WHERE path = $1", table_name);
+        let mut deleted_count = 0;
+        let mut deleted_paths = Vec::new();
+        for path in &self.tombstones {
+            let result = sqlx::query(&delete_query).bind(path).execute(&pool).await;
+            if result.is_ok() {
+                deleted_count += 1;
+                deleted_paths.push(path.clone());
+            }
+        }
+
+        pool.close().await;
+        for path in exported_paths {
+            self.dirty.remove(&path);
+        }
+        for path in deleted_paths {
+            self.tombstones.remove(&path);
+        }
+        Ok((exported_count, deleted_count))
+    }
+
+    pub async fn sync_with_postgres(&mut self, direction: &str) -> SyncStats {
+        let mut stats = SyncStats {
+            imported: 0,
+            exported: 0,
+            skipped: 0,
+            deleted: 0,
+        };
+
+        if direction == "import" || direction == "both" {
+            if let Ok(imported) = self.import_from_postgres_incremental(&self.table_name.clone()).await {
+                stats.imported = imported;
+            }
+        }
+
+        if direction == "export" || direction == "both" {
+            // Captured here, not before the "both"-mode import above ran —
+            // otherwise this mixes a pre-import node count against the
+            // post-import `dirty` set, producing a meaningless `skipped`
+            // figure (and spuriously saturating to 0).
+            let total_before_export = self.data.len();
+            stats.skipped = total_before_export.saturating_sub(self.dirty.len());
+            if let Ok((exported, deleted)) = self.export_to_postgres_incremental(&self.table_name.clone()).await {
+                stats.exported = exported;
+                stats.deleted = deleted;
+            }
+        }
+
+        stats
+    }
+
+    /// Streams this tree to `w` as newline-delimited JSON, one
+    /// `{"path":..,"data":..,"created_at":..,"updated_at":..}` object per
+    /// node, without building the whole dump in memory first. A
+    /// backend-independent counterpart to `export_to_postgres`/
+    /// `export_to_sqlite` for backup, diffing, and moving data between stores.
+    pub fn export_json<W: Write>(&self, mut w: W) -> Result<usize, KbError> {
+        let mut count = 0;
+        for node in self.data.values() {
+            let line = serde_json::json!({
+                "path": node.path,
+                "data": node.data,
+                "created_at": node.created_at,
+                "updated_at": node.updated_at,
+            });
+            serde_json::to_writer(&mut w, &line).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+            w.write_all(b"\n").map_err(|e| KbError::DatabaseError(e.to_string()))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Reads newline-delimited JSON written by `export_json`, calling `store`
+    /// for each line so the usual path validation/versioning still applies.
+    pub fn import_json<R: Read>(&mut self, r: R) -> Result<usize, KbError> {
+        let reader = BufReader::new(r);
+        let mut count = 0;
+        for line in reader.lines() {
+            let line = line.map_err(|e| KbError::DatabaseError(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: Value = serde_json::from_str(&line).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+            let path = record
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| KbError::ValidationError("Missing 'path' field".to_string()))?
+                .to_string();
+            let data = record.get("data").cloned().unwrap_or(Value::Null);
+            let created_at = record.get("created_at").and_then(Value::as_str).map(String::from);
+            let updated_at = record.get("updated_at").and_then(Value::as_str).map(String::from);
+
+            self.store(&path, data, created_at, updated_at)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn get_stats(&self) -> TreeStats {
+        if self.data.is_empty() {
+            return TreeStats {
+                total_nodes: 0,
+                max_depth: 0,
+                avg_depth: 0.0,
+                root_nodes: 0,
+                leaf_nodes: 0,
+            };
+        }
+
+        let mut depths = Vec::new();
+        let mut root_nodes = 0;
+
+        for path in self.data.keys() {
+            let depth = self.nlevel(path);
+            depths.push(depth);
+            if depth == 1 {
+                root_nodes += 1;
+            }
+        }
+
+        // Calculate max depth
+        let max_depth = depths.iter().max().copied().unwrap_or(0);
+        let total_depth: usize = depths.iter().sum();
+
+        // Count leaf nodes (nodes with no children)
+        let mut leaf_nodes = 0;
+        for path in self.data.keys() {
+            let mut has_children = false;
+            for other_path in self.data.keys() {
+                if self.ltree_ancestor(path, other_path) {
+                    has_children = true;
+                    break;
+                }
+            }
+            if !has_children {
+                leaf_nodes += 1;
+            }
+        }
+
+        let avg_depth = if depths.is_empty() {
+            0.0
+        } else {
+            total_depth as f64 / depths.len() as f64
+        };
+
+        TreeStats {
+            total_nodes: self.data.len(),
+            max_depth,
+            avg_depth,
+            root_nodes,
+            leaf_nodes,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
 
     pub fn get_all_paths(&self) -> Vec<String> {
         let mut paths: Vec<String> = self.data.keys().cloned().collect();
         paths.sort();
         paths
     }
+
+    /// Starts a transaction that stages mutations against this tree so they
+    /// can be rolled back atomically if something fails partway through.
+    pub fn begin_transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            db: self,
+            undo_log: Vec::new(),
+        }
+    }
+
+    /// Runs `f` against a [`TxHandle`] that buffers every write in a staging
+    /// map instead of touching `self.data` directly. If `f` calls
+    /// `tx.commit(value)`, the staged writes are merged into the tree and
+    /// `Ok(value)` is returned; if it calls `tx.abort(value)` (or panics), the
+    /// staging map is simply dropped and the tree is left untouched. This
+    /// makes a multi-step mutation like "move root.a to root.b.a" (a remove
+    /// plus an insert) all-or-nothing without an explicit undo log.
+    pub fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut TxHandle) -> TxOutcome<T>,
+    ) -> Result<T, TxError<T>> {
+        let mut tx = TxHandle {
+            db: self,
+            staging: HashMap::new(),
+        };
+        let outcome = f(&mut tx);
+        let TxHandle { staging, .. } = tx;
+
+        match outcome {
+            TxOutcome::Commit(value) => {
+                for (path, entry) in staging {
+                    match entry {
+                        // `restore_node`, not `store` — `TxHandle::insert`
+                        // already computed the right version against the
+                        // staged view, and `store` would bump it a second
+                        // time. Going through it (rather than `self.data`
+                        // directly) keeps indexes and dirty/tombstone
+                        // tracking in sync with this write.
+                        Some(node) => {
+                            let _ = self.restore_node(node);
+                        }
+                        None => {
+                            self.delete(&path);
+                        }
+                    }
+                }
+                Ok(value)
+            }
+            TxOutcome::Abort(value) => Err(TxError::Abort(value)),
+        }
+    }
+}
+
+/// The value a transaction closure hands back to [`BasicConstructDB::transaction`]
+/// via [`TxHandle::commit`] or [`TxHandle::abort`].
+pub enum TxOutcome<T> {
+    Commit(T),
+    Abort(T),
+}
+
+/// Returned by [`BasicConstructDB::transaction`] when the closure aborts.
+#[derive(Debug)]
+pub enum TxError<T> {
+    Abort(T),
+}
+
+/// A staged handle into a [`BasicConstructDB::transaction`] closure. Reads see
+/// a view of the tree overlaid with this transaction's own pending writes;
+/// nothing is applied to the underlying tree until the closure calls
+/// `commit()`.
+pub struct TxHandle<'a> {
+    db: &'a BasicConstructDB,
+    staging: HashMap<String, Option<TreeNode>>,
+}
+
+impl<'a> TxHandle<'a> {
+    /// Stages an insert/update of `path`, without touching the underlying tree.
+    pub fn insert(
+        &mut self,
+        path: &str,
+        data: Value,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+    ) -> Result<(), KbError> {
+        if !self.db.validate_path(path) {
+            return Err(KbError::InvalidPath(path.to_string()));
+        }
+        let version = self.get_node(path).map(|node| node.version + 1).unwrap_or(1);
+        self.staging.insert(
+            path.to_string(),
+            Some(TreeNode {
+                path: path.to_string(),
+                data,
+                created_at,
+                updated_at,
+                version,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Reads `path`, preferring this transaction's own staged writes over the
+    /// underlying tree.
+    pub fn get(&self, path: &str) -> Option<Value> {
+        self.get_node(path).map(|node| node.data)
+    }
+
+    fn get_node(&self, path: &str) -> Option<TreeNode> {
+        match self.staging.get(path) {
+            Some(Some(node)) => Some(node.clone()),
+            Some(None) => None,
+            None => self.db.data.get(path).cloned(),
+        }
+    }
+
+    /// Stages a removal of `path`, without touching the underlying tree.
+    pub fn remove(&mut self, path: &str) {
+        self.staging.insert(path.to_string(), None);
+    }
+
+    /// Ends the closure with success: `transaction()` merges every staged
+    /// write into the tree and returns `Ok(value)`.
+    pub fn commit<T>(&self, value: T) -> TxOutcome<T> {
+        TxOutcome::Commit(value)
+    }
+
+    /// Ends the closure with failure: `transaction()` discards the staging
+    /// map and returns `Err(TxError::Abort(value))`.
+    pub fn abort<T>(&self, value: T) -> TxOutcome<T> {
+        TxOutcome::Abort(value)
+    }
+}
+
+/// One entry in a [`Transaction`]'s undo log: either the pre-mutation state of
+/// a path (`None` means the path didn't exist, i.e. a tombstone), or a named
+/// savepoint marker pushed by [`Transaction::savepoint`].
+enum UndoEntry {
+    Node(String, Option<TreeNode>),
+    Savepoint(String),
+}
+
+/// A staged batch of mutations over a [`BasicConstructDB`]. Mutating calls on
+/// the transaction apply immediately to the underlying tree but also append
+/// the overwritten state to an undo log, so `rollback()` (or
+/// `rollback_to_savepoint()`) can restore every touched path. This makes
+/// operations like `add_subtree`, which can fail midway and otherwise leave
+/// partial data, all-or-nothing.
+pub struct Transaction<'a> {
+    db: &'a mut BasicConstructDB,
+    undo_log: Vec<UndoEntry>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Records the current state of `path` so it can be restored later.
+    fn record(&mut self, path: &str) {
+        let prior = self.db.data.get(path).cloned();
+        self.undo_log.push(UndoEntry::Node(path.to_string(), prior));
+    }
+
+    fn restore(&mut self, path: String, prior: Option<TreeNode>) {
+        // Through `restore_node`/`delete` rather than `self.db.data` directly,
+        // so secondary indexes and dirty/tombstone tracking follow the undo.
+        match prior {
+            Some(node) => {
+                let _ = self.db.restore_node(node);
+            }
+            None => {
+                self.db.delete(&path);
+            }
+        }
+    }
+
+    pub fn store(
+        &mut self,
+        path: &str,
+        data: Value,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+    ) -> Result<u64, KbError> {
+        self.record(path);
+        self.db.store(path, data, created_at, updated_at)
+    }
+
+    pub fn delete(&mut self, path: &str) -> bool {
+        self.record(path);
+        self.db.delete(path)
+    }
+
+    pub fn delete_subtree(&mut self, path: &str) -> usize {
+        let to_delete: Vec<String> = self
+            .db
+            .data
+            .keys()
+            .filter(|stored_path| *stored_path == path || self.db.ltree_descendant(stored_path, path))
+            .cloned()
+            .collect();
+
+        for touched_path in &to_delete {
+            self.record(touched_path);
+        }
+
+        let count = to_delete.len();
+        for touched_path in to_delete {
+            if let Some(node) = self.db.data.get(&touched_path) {
+                let data = node.data.clone();
+                self.db.remove_indexes_for(&touched_path, &data);
+            }
+            self.db.data.remove(&touched_path);
+            self.db.dirty.remove(&touched_path);
+            self.db.tombstones.insert(touched_path);
+        }
+        count
+    }
+
+    pub fn add_subtree(&mut self, path: &str, subtree: &[QueryResult]) -> Result<(), KbError> {
+        if !self.db.validate_path(path) {
+            return Err(KbError::InvalidPath(path.to_string()));
+        }
+        if !self.db.exists(path) {
+            return Err(KbError::PathNotFound(path.to_string()));
+        }
+
+        for node in subtree {
+            let new_path = format!("{}.{}", path, node.path);
+            self.store(&new_path, node.data.clone(), node.created_at.clone(), node.updated_at.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Pushes a named marker into the undo log that `rollback_to_savepoint`
+    /// and `release_savepoint` can refer back to.
+    pub fn savepoint(&mut self, name: &str) {
+        self.undo_log.push(UndoEntry::Savepoint(name.to_string()));
+    }
+
+    /// Undoes every mutation recorded since `savepoint(name)`, leaving the
+    /// savepoint itself in place so it can be rolled back to again.
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> Result<(), KbError> {
+        let pos = self
+            .undo_log
+            .iter()
+            .rposition(|entry| matches!(entry, UndoEntry::Savepoint(marker) if marker == name))
+            .ok_or_else(|| KbError::ValidationError(format!("No savepoint named '{}'", name)))?;
+
+        while self.undo_log.len() > pos + 1 {
+            if let UndoEntry::Node(path, prior) = self.undo_log.pop().unwrap() {
+                self.restore(path, prior);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets a savepoint without undoing anything, the way a nested
+    /// transaction's changes get folded into its parent on success.
+    pub fn release_savepoint(&mut self, name: &str) -> Result<(), KbError> {
+        let pos = self
+            .undo_log
+            .iter()
+            .rposition(|entry| matches!(entry, UndoEntry::Savepoint(marker) if marker == name))
+            .ok_or_else(|| KbError::ValidationError(format!("No savepoint named '{}'", name)))?;
+        self.undo_log.remove(pos);
+        Ok(())
+    }
+
+    /// Keeps every staged mutation. The tree already reflects them, so this
+    /// simply discards the undo log.
+    pub fn commit(self) {}
+
+    /// Restores every path touched since `begin_transaction()`, undoing the
+    /// transaction as a whole.
+    pub fn rollback(mut self) {
+        while let Some(entry) = self.undo_log.pop() {
+            if let UndoEntry::Node(path, prior) = entry {
+                self.restore(path, prior);
+            }
+        }
+    }
+}
+
+/// Builds a query over a path pattern and zero or more `query_where`-style
+/// equality predicates, intersecting each predicate's candidate path set
+/// (from its secondary index, or a full scan if it isn't indexed) before
+/// applying the path pattern. Built via [`BasicConstructDB::query_builder`].
+pub struct QueryBuilder<'a> {
+    db: &'a BasicConstructDB,
+    pattern: Option<String>,
+    predicates: Vec<(String, Value)>,
+}
+
+impl<'a> QueryBuilder<'a> {
+    /// Restricts results to paths matching an ltree pattern (`*`, `**`).
+    pub fn path_pattern(mut self, pattern: &str) -> Self {
+        self.pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Adds an equality predicate on a JSON-pointer field of `data`.
+    pub fn where_eq(mut self, field: &str, value: Value) -> Self {
+        self.predicates.push((field.to_string(), value));
+        self
+    }
+
+    /// Evaluates the built query.
+    pub fn run(self) -> Result<Vec<QueryResult>, KbError> {
+        let mut candidates: Option<HashSet<String>> = None;
+        for (field, value) in &self.predicates {
+            let matches: HashSet<String> = self
+                .db
+                .query_where(field, value)
+                .into_iter()
+                .map(|result| result.path)
+                .collect();
+            candidates = Some(match candidates {
+                Some(existing) => existing.intersection(&matches).cloned().collect(),
+                None => matches,
+            });
+        }
+
+        let paths: Vec<String> = match candidates {
+            Some(set) => set.into_iter().collect(),
+            None => self.db.data.keys().cloned().collect(),
+        };
+
+        let mut results = Vec::new();
+        for path in paths {
+            if let Some(pattern) = &self.pattern {
+                if !self.db.ltree_match(&path, pattern)? {
+                    continue;
+                }
+            }
+            if let Some(node) = self.db.data.get(&path) {
+                results.push(QueryResult {
+                    path: path.clone(),
+                    data: node.data.clone(),
+                    created_at: node.created_at.clone(),
+                    updated_at: node.updated_at.clone(),
+                });
+            }
+        }
+        Ok(results)
+    }
 }
 
 #[cfg(test)]
@@ -954,11 +2124,13 @@ mod tests {
         let _ = db.store("root.child1.grandchild", serde_json::json!({"type": "grandchild"}), None, None);
 
         // Test pattern matching
-        let results = db.query("root.*");
+        let results = db.query("root.*").unwrap();
         assert_eq!(results.len(), 2); // child1 and child2
 
-        let results = db.query("root.**");
-        assert_eq!(results.len(), 3); // child1, child2, and grandchild
+        // `**` matches zero or more trailing levels, so this also matches
+        // "root" itself, matching Postgres ltree's `*{0,}` semantics.
+        let results = db.query("root.**").unwrap();
+        assert_eq!(results.len(), 4); // root, child1, child2, and grandchild
 
         // Test ancestor/descendant queries
         let ancestors = db.query_ancestors("root.child1.grandchild").unwrap();
@@ -971,6 +2143,61 @@ mod tests {
         assert_eq!(subtree.len(), 2); // child1 and grandchild
     }
 
+    #[test]
+    fn test_ltree_match_quantifiers() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        let _ = db.store("root.a.b.leaf", serde_json::json!({}), None, None);
+        let _ = db.store("root.a.leaf", serde_json::json!({}), None, None);
+        let _ = db.store("root.a.b.c.leaf", serde_json::json!({}), None, None);
+
+        // *{2} requires exactly two levels between root and leaf.
+        assert!(db.ltree_match("root.a.b.leaf", "root.*{2}.leaf").unwrap());
+        assert!(!db.ltree_match("root.a.leaf", "root.*{2}.leaf").unwrap());
+
+        // *{1,2} allows one or two.
+        assert!(db.ltree_match("root.a.leaf", "root.*{1,2}.leaf").unwrap());
+        assert!(db.ltree_match("root.a.b.leaf", "root.*{1,2}.leaf").unwrap());
+        assert!(!db.ltree_match("root.a.b.c.leaf", "root.*{1,2}.leaf").unwrap());
+
+        // *{2,} requires two or more.
+        assert!(!db.ltree_match("root.a.leaf", "root.*{2,}.leaf").unwrap());
+        assert!(db.ltree_match("root.a.b.c.leaf", "root.*{2,}.leaf").unwrap());
+    }
+
+    #[test]
+    fn test_ltree_match_alternation_prefix_and_negation() {
+        let db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        // Alternation: a label matching any of the alternatives.
+        assert!(db.ltree_match("root.dev", "root.dev|prod").unwrap());
+        assert!(db.ltree_match("root.prod", "root.dev|prod").unwrap());
+        assert!(!db.ltree_match("root.staging", "root.dev|prod").unwrap());
+
+        // Prefix matching via a trailing `*` or `@` on a label.
+        assert!(db.ltree_match("root.devops", "root.dev*").unwrap());
+        assert!(db.ltree_match("root.devops", "root.dev@").unwrap());
+        assert!(!db.ltree_match("root.ops", "root.dev*").unwrap());
+
+        // Negation: a label must NOT match any alternative.
+        assert!(db.ltree_match("root.prod", "root.!dev").unwrap());
+        assert!(!db.ltree_match("root.dev", "root.!dev").unwrap());
+    }
+
     #[test]
     fn test_delete_operations() {
         let mut db = BasicConstructDB::new(
@@ -1033,6 +2260,379 @@ mod tests {
         assert_eq!(lca2, None);
     }
 
+    #[test]
+    fn test_versioning_and_compare_and_store() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        // First store starts at version 1
+        let version = db.store("root", serde_json::json!({"n": 1}), None, None).unwrap();
+        assert_eq!(version, 1);
+
+        // Overwriting via store() bumps the version
+        let version = db.store("root", serde_json::json!({"n": 2}), None, None).unwrap();
+        assert_eq!(version, 2);
+
+        let (data, version) = db.get_versioned("root").unwrap();
+        assert_eq!(data, serde_json::json!({"n": 2}));
+        assert_eq!(version, 2);
+
+        // CAS with the expected version succeeds and bumps the version again
+        let version = db.compare_and_store("root", serde_json::json!({"n": 3}), 2).unwrap();
+        assert_eq!(version, 3);
+
+        // CAS with a stale expected version is rejected
+        let err = db.compare_and_store("root", serde_json::json!({"n": 4}), 2).unwrap_err();
+        match err {
+            KbError::VersionConflict { path, expected, actual } => {
+                assert_eq!(path, "root");
+                assert_eq!(expected, 2);
+                assert_eq!(actual, 3);
+            }
+            _ => panic!("expected VersionConflict"),
+        }
+
+        // The rejected CAS must not have changed the stored data or version
+        let (data, version) = db.get_versioned("root").unwrap();
+        assert_eq!(data, serde_json::json!({"n": 3}));
+        assert_eq!(version, 3);
+    }
+
+    #[test]
+    fn test_query_reuses_cached_matcher() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        let _ = db.store("root.child1", serde_json::json!({"type": "child"}), None, None);
+        let _ = db.store("root.child2", serde_json::json!({"type": "child"}), None, None);
+
+        // Repeated queries with the same pattern should hit the matcher cache
+        // and return identical results each time.
+        for _ in 0..3 {
+            let results = db.query("root.*").unwrap();
+            assert_eq!(results.len(), 2);
+        }
+
+        // An invalid repetition range (min > max) surfaces a typed error
+        // instead of silently matching nothing.
+        assert!(db.query("root.*{5,2}").is_err());
+    }
+
+    #[test]
+    fn test_ltxtquery_boolean_parser() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        let _ = db.store("root.admin.settings", serde_json::json!({}), None, None);
+
+        // Plain word match
+        assert!(db.ltxtquery_match("root.admin.settings", "admin").unwrap());
+        assert!(!db.ltxtquery_match("root.admin.settings", "missing").unwrap());
+
+        // & and | combine, ! negates, and parentheses override precedence
+        assert!(db.ltxtquery_match("root.admin.settings", "root & admin").unwrap());
+        assert!(db.ltxtquery_match("root.admin.settings", "missing | admin").unwrap());
+        assert!(db.ltxtquery_match("root.admin.settings", "!missing").unwrap());
+        assert!(db.ltxtquery_match("root.admin.settings", "(root | missing) & admin").unwrap());
+        assert!(!db.ltxtquery_match("root.admin.settings", "!(root | missing) & admin").unwrap());
+
+        // `word*` matches any label with that prefix
+        assert!(db.ltxtquery_match("root.admin.settings", "sett*").unwrap());
+        assert!(!db.ltxtquery_match("root.admin.settings", "zzz*").unwrap());
+
+        // Malformed input (unbalanced parens) is a typed validation error
+        assert!(db.ltxtquery_match("root.admin.settings", "(admin & root").is_err());
+
+        // query_ltxtquery routes through the same parser
+        let results = db.query_ltxtquery("admin & root").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, "root.admin.settings");
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        let _ = db.store("root", serde_json::json!({"n": 1}), None, None);
+
+        {
+            let mut txn = db.begin_transaction();
+            txn.store("root", serde_json::json!({"n": 2}), None, None).unwrap();
+            txn.store("root.child", serde_json::json!({"n": 3}), None, None).unwrap();
+            txn.rollback();
+        }
+
+        // Rolling back restores the original value and removes the new path
+        assert_eq!(db.get("root").unwrap(), Some(serde_json::json!({"n": 1})));
+        assert!(!db.exists("root.child"));
+    }
+
+    #[test]
+    fn test_transaction_commit() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        {
+            let mut txn = db.begin_transaction();
+            txn.store("root", serde_json::json!({"n": 1}), None, None).unwrap();
+            txn.commit();
+        }
+
+        assert_eq!(db.get("root").unwrap(), Some(serde_json::json!({"n": 1})));
+    }
+
+    #[test]
+    fn test_transaction_savepoints() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        {
+            let mut txn = db.begin_transaction();
+            txn.store("root", serde_json::json!({"n": 1}), None, None).unwrap();
+            txn.savepoint("after_root");
+            txn.store("root.child", serde_json::json!({"n": 2}), None, None).unwrap();
+
+            // Rolling back to the savepoint undoes root.child but keeps root
+            txn.rollback_to_savepoint("after_root").unwrap();
+
+            txn.store("root.child2", serde_json::json!({"n": 3}), None, None).unwrap();
+            txn.release_savepoint("after_root").unwrap();
+            txn.commit();
+        }
+
+        assert_eq!(db.get("root").unwrap(), Some(serde_json::json!({"n": 1})));
+        assert!(db.exists("root.child2"));
+        assert!(!db.exists("root.child"));
+    }
+
+    #[test]
+    fn test_tx_handle_commit_moves_subtree_atomically() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+        db.store("root.a", serde_json::json!({"n": 1}), None, None).unwrap();
+
+        let result = db.transaction(|tx| {
+            let data = match tx.get("root.a") {
+                Some(data) => data,
+                None => return tx.abort("root.a missing"),
+            };
+            tx.remove("root.a");
+            if tx.insert("root.b.a", data, None, None).is_err() {
+                return tx.abort("invalid destination path");
+            }
+            tx.commit("moved")
+        });
+
+        assert_eq!(result.unwrap(), "moved");
+        assert!(!db.exists("root.a"));
+        assert_eq!(db.get("root.b.a").unwrap(), Some(serde_json::json!({"n": 1})));
+    }
+
+    #[test]
+    fn test_tx_handle_abort_leaves_tree_untouched() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+        db.store("root.a", serde_json::json!({"n": 1}), None, None).unwrap();
+
+        let result = db.transaction(|tx| {
+            tx.remove("root.a");
+            tx.insert("root.b", serde_json::json!({"n": 2}), None, None).unwrap();
+            tx.abort("changed my mind")
+        });
+
+        assert!(matches!(result, Err(TxError::Abort("changed my mind"))));
+        assert_eq!(db.get("root.a").unwrap(), Some(serde_json::json!({"n": 1})));
+        assert!(!db.exists("root.b"));
+    }
+
+    #[test]
+    fn test_dirty_and_tombstone_tracking() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        db.store("root.a", serde_json::json!({"n": 1}), None, None).unwrap();
+        db.store("root.b", serde_json::json!({"n": 2}), None, None).unwrap();
+        assert_eq!(db.dirty.len(), 2);
+        assert!(db.tombstones.is_empty());
+
+        assert!(db.delete("root.a"));
+        assert!(!db.dirty.contains("root.a"));
+        assert!(db.tombstones.contains("root.a"));
+
+        // Restoring a tombstoned path clears it back out of tombstones.
+        db.store("root.a", serde_json::json!({"n": 3}), None, None).unwrap();
+        assert!(db.dirty.contains("root.a"));
+        assert!(!db.tombstones.contains("root.a"));
+    }
+
+    #[test]
+    fn test_export_import_json_round_trip() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        db.store("root", serde_json::json!({"n": 1}), Some("2024-01-01".to_string()), Some("2024-01-02".to_string())).unwrap();
+        db.store("root.child", serde_json::json!({"n": 2}), None, None).unwrap();
+
+        let mut dump = Vec::new();
+        let exported = db.export_json(&mut dump).unwrap();
+        assert_eq!(exported, 2);
+
+        let mut paths_before = db.get_all_paths();
+        paths_before.sort();
+
+        db.clear();
+        assert_eq!(db.size(), 0);
+
+        let imported = db.import_json(dump.as_slice()).unwrap();
+        assert_eq!(imported, 2);
+
+        let mut paths_after = db.get_all_paths();
+        paths_after.sort();
+        assert_eq!(paths_before, paths_after);
+
+        let root = db.get_node("root").unwrap().unwrap();
+        assert_eq!(root.created_at, Some("2024-01-01".to_string()));
+        assert_eq!(root.updated_at, Some("2024-01-02".to_string()));
+        assert_eq!(db.get("root.child").unwrap(), Some(serde_json::json!({"n": 2})));
+    }
+
+    #[test]
+    fn test_create_index_backfills_and_tracks_updates() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+
+        db.store("root.config.a", serde_json::json!({"enabled": true}), None, None).unwrap();
+        db.store("root.config.b", serde_json::json!({"enabled": false}), None, None).unwrap();
+
+        // create_index backfills from nodes that already existed.
+        db.create_index("/enabled");
+        let enabled = db.query_where("/enabled", &serde_json::json!(true));
+        assert_eq!(enabled.len(), 1);
+        assert_eq!(enabled[0].path, "root.config.a");
+
+        // Updating a node's field moves it between index buckets.
+        db.store("root.config.b", serde_json::json!({"enabled": true}), None, None).unwrap();
+        let enabled = db.query_where("/enabled", &serde_json::json!(true));
+        assert_eq!(enabled.len(), 2);
+
+        // Deleting a node drops it from the index.
+        db.delete("root.config.a");
+        let enabled = db.query_where("/enabled", &serde_json::json!(true));
+        assert_eq!(enabled.len(), 1);
+        assert_eq!(enabled[0].path, "root.config.b");
+    }
+
+    #[test]
+    fn test_query_where_falls_back_to_full_scan_when_unindexed() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+        db.store("root.a", serde_json::json!({"type": "widget"}), None, None).unwrap();
+        db.store("root.b", serde_json::json!({"type": "gadget"}), None, None).unwrap();
+
+        let widgets = db.query_where("/type", &serde_json::json!("widget"));
+        assert_eq!(widgets.len(), 1);
+        assert_eq!(widgets[0].path, "root.a");
+    }
+
+    #[test]
+    fn test_query_builder_combines_path_pattern_and_predicates() {
+        let mut db = BasicConstructDB::new(
+            "localhost".to_string(),
+            5432,
+            "test".to_string(),
+            "user".to_string(),
+            "pass".to_string(),
+            "test_table".to_string(),
+        );
+        db.store("root.config.a", serde_json::json!({"enabled": true}), None, None).unwrap();
+        db.store("root.config.b", serde_json::json!({"enabled": false}), None, None).unwrap();
+        db.store("other.config.c", serde_json::json!({"enabled": true}), None, None).unwrap();
+        db.create_index("/enabled");
+
+        let results = db
+            .query_builder()
+            .path_pattern("root.**")
+            .where_eq("/enabled", serde_json::json!(true))
+            .run()
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, "root.config.a");
+    }
+
     #[test]
     fn test_tree_stats() {
         let mut db = BasicConstructDB::new(
@@ -1,11 +1,17 @@
 use std::collections::HashMap;
 use serde_json::Value;
-use crate::basic_memory_module::{BasicConstructDB, KbError}; // Assuming the previous module is imported
-
-/// ConstructMemDB extends BasicConstructDB with knowledge base management and composite path tracking
-pub struct ConstructMemDB {
-    /// Embedded BasicConstructDB for inheritance-like behavior
-    pub basic_db: BasicConstructDB,
+use crate::basic_memory_module::{BasicConstructDB, KbError, TreeNode}; // Assuming the previous module is imported
+use crate::kb_backend::KbBackend;
+use crate::kb_path_trie::PathTrie;
+
+/// ConstructMemDB extends BasicConstructDB with knowledge base management and composite path tracking.
+/// Generic over the node-storage backend `B` (see [`KbBackend`]) so it can be
+/// built against the in-memory `BasicConstructDB` (the default) or a durable
+/// backend such as [`SledKbBackend`](crate::kb_backend::SledKbBackend) — only
+/// node data flows through `B`, composite-path tracking always stays here.
+pub struct ConstructMemDB<B: KbBackend = BasicConstructDB> {
+    /// Embedded storage backend for inheritance-like behavior
+    pub basic_db: B,
     /// Currently selected knowledge base name
     kb_name: Option<String>,
     /// Working knowledge base
@@ -14,6 +20,13 @@ pub struct ConstructMemDB {
     composite_path: HashMap<String, Vec<String>>,
     /// Tracks existing paths in each KB
     composite_path_values: HashMap<String, HashMap<String, bool>>,
+    /// Radix trie over each KB's dot-segmented paths, kept in sync with
+    /// `composite_path_values` so `descendants`/`ancestors`/`match_paths`
+    /// can walk only the relevant branch instead of scanning the HashMap.
+    path_tries: HashMap<String, PathTrie>,
+    /// Per-KB description, tracked here rather than in `basic_db` since
+    /// `KbBackend` is scoped to node storage only.
+    kb_descriptions: HashMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -28,6 +41,7 @@ pub enum ConstructMemError {
     NotEnoughElements,
     AssertionError(String),
     InstallationCheckFailed(String),
+    Corruption(String),
 }
 
 impl std::fmt::Display for ConstructMemError {
@@ -43,6 +57,7 @@ impl std::fmt::Display for ConstructMemError {
             ConstructMemError::NotEnoughElements => write!(f, "Cannot leave a header node: not enough elements in path"),
             ConstructMemError::AssertionError(msg) => write!(f, "Assertion error: {}", msg),
             ConstructMemError::InstallationCheckFailed(msg) => write!(f, "Installation check failed: {}", msg),
+            ConstructMemError::Corruption(msg) => write!(f, "Corrupt ConstructMemDB snapshot: {}", msg),
         }
     }
 }
@@ -55,8 +70,27 @@ impl From<KbError> for ConstructMemError {
     }
 }
 
-impl ConstructMemDB {
-    /// Creates a new ConstructMemDB instance
+/// How `add_or_merge_header_node` should combine incoming `node_data` with
+/// whatever is already stored at that path, borrowing RocksDB's
+/// merge-operator concept so repeated visits to the same path accumulate
+/// correctly instead of requiring a delete-and-rewrite.
+pub enum MergePolicy {
+    /// Fail with `PathAlreadyExists`, the behavior `add_header_node` has
+    /// always had.
+    Reject,
+    /// Discard the existing `node_data` entirely and store the incoming one.
+    Replace,
+    /// Recursively merge the two JSON objects: incoming scalars and arrays
+    /// overwrite, nested objects merge key-by-key.
+    DeepMerge,
+    /// Combines existing and incoming data via a user-supplied associative
+    /// merge function.
+    Custom(Box<dyn Fn(&Value, &Value) -> Value>),
+}
+
+impl ConstructMemDB<BasicConstructDB> {
+    /// Creates a new ConstructMemDB instance backed by the default in-memory
+    /// `BasicConstructDB`.
     pub fn new(
         host: String,
         port: u16,
@@ -65,12 +99,23 @@ impl ConstructMemDB {
         password: String,
         database: String,
     ) -> Self {
+        Self::new_with_backend(BasicConstructDB::new(host, port, dbname, user, password, database))
+    }
+}
+
+impl<B: KbBackend> ConstructMemDB<B> {
+    /// Creates a new ConstructMemDB instance on top of any [`KbBackend`],
+    /// e.g. a durable store such as `SledKbBackend` instead of the default
+    /// in-memory `BasicConstructDB`.
+    pub fn new_with_backend(backend: B) -> Self {
         Self {
-            basic_db: BasicConstructDB::new(host, port, dbname, user, password, database),
+            basic_db: backend,
             kb_name: None,
             working_kb: None,
             composite_path: HashMap::new(),
             composite_path_values: HashMap::new(),
+            path_tries: HashMap::new(),
+            kb_descriptions: HashMap::new(),
         }
     }
 
@@ -84,9 +129,8 @@ impl ConstructMemDB {
         // Initialize composite path structures
         self.composite_path.insert(kb_name.clone(), vec![kb_name.clone()]);
         self.composite_path_values.insert(kb_name.clone(), HashMap::new());
-
-        // Call parent method
-        self.basic_db.add_kb(&kb_name, &description)?;
+        self.path_tries.insert(kb_name.clone(), PathTrie::new());
+        self.kb_descriptions.insert(kb_name, description);
         Ok(())
     }
 
@@ -101,11 +145,47 @@ impl ConstructMemDB {
 
     /// Adds a header node to the knowledge base
     pub fn add_header_node(
+        &mut self,
+        link: String,
+        node_name: String,
+        node_data: HashMap<String, Value>,
+        description: Option<String>,
+    ) -> Result<(), ConstructMemError> {
+        self.add_or_merge_header_node(link, node_name, node_data, description, MergePolicy::Reject)
+    }
+
+    /// Recursively merges two JSON values: two objects merge key-by-key
+    /// (with `incoming` taking precedence on conflicts), anything else is
+    /// fully replaced by `incoming`.
+    fn deep_merge(existing: &Value, incoming: &Value) -> Value {
+        match (existing, incoming) {
+            (Value::Object(existing_map), Value::Object(incoming_map)) => {
+                let mut merged = existing_map.clone();
+                for (key, incoming_value) in incoming_map {
+                    let merged_value = match merged.get(key) {
+                        Some(existing_value) => Self::deep_merge(existing_value, incoming_value),
+                        None => incoming_value.clone(),
+                    };
+                    merged.insert(key.clone(), merged_value);
+                }
+                Value::Object(merged)
+            }
+            _ => incoming.clone(),
+        }
+    }
+
+    /// Like `add_header_node`, but lets the caller choose how to handle a
+    /// path that was already written instead of always failing with
+    /// `PathAlreadyExists` — see [`MergePolicy`]. This supports
+    /// incremental/idempotent KB construction where the same node is
+    /// visited more than once.
+    pub fn add_or_merge_header_node(
         &mut self,
         link: String,
         node_name: String,
         mut node_data: HashMap<String, Value>,
         description: Option<String>,
+        policy: MergePolicy,
     ) -> Result<(), ConstructMemError> {
         let working_kb = self.working_kb.as_ref()
             .ok_or(ConstructMemError::NoWorkingKB)?
@@ -124,30 +204,41 @@ impl ConstructMemDB {
             path.push(node_name);
             let node_path = path.join(".");
 
-            // Check if path already exists
-            if let Some(path_values) = self.composite_path_values.get(&working_kb) {
-                if *path_values.get(&node_path).unwrap_or(&false) {
-                    return Err(ConstructMemError::PathAlreadyExists(node_path));
-                }
-            }
-
-            // Mark path as used
-            if let Some(path_values) = self.composite_path_values.get_mut(&working_kb) {
-                path_values.insert(node_path, true);
-            }
+            let already_exists = self.composite_path_values.get(&working_kb)
+                .map(|path_values| *path_values.get(&node_path).unwrap_or(&false))
+                .unwrap_or(false);
 
-            // Store in the underlying BasicConstructDB
-            let path_string = path.join(".");
-            println!("path: {}", path_string);
-            
             // Convert HashMap<String, Value> to Value::Object
-            let node_data_value = Value::Object(
+            let incoming_value = Value::Object(
                 node_data.into_iter()
                     .map(|(k, v)| (k, v))
                     .collect()
             );
-            
-            self.basic_db.store(&path_string, node_data_value, None, None)?;
+
+            let node_data_value = if already_exists {
+                match policy {
+                    MergePolicy::Reject => return Err(ConstructMemError::PathAlreadyExists(node_path)),
+                    MergePolicy::Replace => incoming_value,
+                    MergePolicy::DeepMerge => {
+                        let existing = self.basic_db.get_node(&node_path)?.map(|node| node.data).unwrap_or(Value::Null);
+                        Self::deep_merge(&existing, &incoming_value)
+                    }
+                    MergePolicy::Custom(merge_fn) => {
+                        let existing = self.basic_db.get_node(&node_path)?.map(|node| node.data).unwrap_or(Value::Null);
+                        merge_fn(&existing, &incoming_value)
+                    }
+                }
+            } else {
+                incoming_value
+            };
+
+            // Mark path as used
+            if let Some(path_values) = self.composite_path_values.get_mut(&working_kb) {
+                path_values.insert(node_path.clone(), true);
+            }
+            self.path_tries.entry(working_kb.clone()).or_default().insert(&node_path);
+
+            self.basic_db.store(&node_path, node_data_value, None, None)?;
         }
 
         Ok(())
@@ -268,13 +359,13 @@ impl ConstructMemDB {
         self.composite_path.keys().cloned().collect()
     }
 
-    /// Gets a reference to the underlying BasicConstructDB
-    pub fn basic_db(&self) -> &BasicConstructDB {
+    /// Gets a reference to the underlying storage backend
+    pub fn basic_db(&self) -> &B {
         &self.basic_db
     }
 
-    /// Gets a mutable reference to the underlying BasicConstructDB
-    pub fn basic_db_mut(&mut self) -> &mut BasicConstructDB {
+    /// Gets a mutable reference to the underlying storage backend
+    pub fn basic_db_mut(&mut self) -> &mut B {
         &mut self.basic_db
     }
 
@@ -285,6 +376,8 @@ impl ConstructMemDB {
         self.working_kb = None;
         self.composite_path.clear();
         self.composite_path_values.clear();
+        self.path_tries.clear();
+        self.kb_descriptions.clear();
     }
 
     /// Gets statistics for a specific knowledge base
@@ -313,6 +406,40 @@ impl ConstructMemDB {
             .unwrap_or(false)
     }
 
+    /// Every stored path in `kb_name` at or under `prefix` (an `ltree @>`
+    /// query), backed by that KB's radix trie instead of a HashMap scan. An
+    /// empty or root-label `prefix` returns every path in the KB.
+    pub fn descendants(&self, kb_name: &str, prefix: &str) -> Vec<String> {
+        let Some(trie) = self.path_tries.get(kb_name) else {
+            return Vec::new();
+        };
+        if prefix.is_empty() || prefix == kb_name {
+            trie.descendants("")
+        } else {
+            trie.descendants(prefix)
+        }
+    }
+
+    /// Every stored path in `kb_name` that is a strict ancestor of `path`.
+    pub fn ancestors(&self, kb_name: &str, path: &str) -> Vec<String> {
+        self.path_tries.get(kb_name).map(|trie| trie.ancestors(path)).unwrap_or_default()
+    }
+
+    /// `lquery`-style pattern match over `kb_name`'s stored paths: `*`
+    /// matches exactly one label, `**` matches zero or more. An empty
+    /// `pattern` returns the KB root itself rather than nothing, since the
+    /// root isn't stored as a path in the trie.
+    pub fn match_paths(&self, kb_name: &str, pattern: &str) -> Vec<String> {
+        if pattern.is_empty() {
+            return if self.composite_path.contains_key(kb_name) {
+                vec![kb_name.to_string()]
+            } else {
+                Vec::new()
+            };
+        }
+        self.path_tries.get(kb_name).map(|trie| trie.match_paths(pattern)).unwrap_or_default()
+    }
+
     /// Removes a knowledge base entirely
     pub fn remove_kb(&mut self, kb_name: &str) -> Result<(), ConstructMemError> {
         if !self.composite_path.contains_key(kb_name) {
@@ -327,6 +454,8 @@ impl ConstructMemDB {
         // Remove from tracking structures
         self.composite_path.remove(kb_name);
         self.composite_path_values.remove(kb_name);
+        self.path_tries.remove(kb_name);
+        self.kb_descriptions.remove(kb_name);
 
         // Remove all data from the basic DB that belongs to this KB
         let kb_prefix = format!("{}.", kb_name);
@@ -341,10 +470,432 @@ impl ConstructMemDB {
 
         Ok(())
     }
+
+    const SNAPSHOT_MAGIC: &'static [u8; 4] = b"CMDB";
+    const SNAPSHOT_VERSION: u16 = 1;
+
+    fn write_blob(out: &mut Vec<u8>, blob: &[u8]) {
+        out.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+        out.extend_from_slice(blob);
+    }
+
+    fn read_blob<'b>(bytes: &'b [u8], pos: &mut usize) -> Result<&'b [u8], ConstructMemError> {
+        if *pos + 4 > bytes.len() {
+            return Err(ConstructMemError::Corruption("truncated length prefix".to_string()));
+        }
+        let len = u32::from_le_bytes(bytes[*pos..*pos + 4].try_into().unwrap()) as usize;
+        *pos += 4;
+        if *pos + len > bytes.len() {
+            return Err(ConstructMemError::Corruption("truncated record".to_string()));
+        }
+        let blob = &bytes[*pos..*pos + len];
+        *pos += len;
+        Ok(blob)
+    }
+
+    fn read_string(bytes: &[u8], pos: &mut usize) -> Result<String, ConstructMemError> {
+        let blob = Self::read_blob(bytes, pos)?;
+        String::from_utf8(blob.to_vec()).map_err(|e| ConstructMemError::Corruption(e.to_string()))
+    }
+
+    fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, ConstructMemError> {
+        if *pos + 4 > bytes.len() {
+            return Err(ConstructMemError::Corruption("truncated count".to_string()));
+        }
+        let value = u32::from_le_bytes(bytes[*pos..*pos + 4].try_into().unwrap());
+        *pos += 4;
+        Ok(value)
+    }
+
+    fn read_u8(bytes: &[u8], pos: &mut usize) -> Result<u8, ConstructMemError> {
+        if *pos >= bytes.len() {
+            return Err(ConstructMemError::Corruption("truncated flag".to_string()));
+        }
+        let value = bytes[*pos];
+        *pos += 1;
+        Ok(value)
+    }
+
+    /// Serializes the entire live state — `working_kb`, every KB's path
+    /// stack and path-value set, and every stored node's JSON data — into a
+    /// single self-describing buffer: a magic/version header followed by
+    /// one length-prefixed record per KB. Round-trips with [`from_bytes`](Self::from_bytes),
+    /// making a KB portable between processes without a database backend.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(Self::SNAPSHOT_MAGIC);
+        out.extend_from_slice(&Self::SNAPSHOT_VERSION.to_le_bytes());
+
+        match &self.working_kb {
+            Some(kb) => {
+                out.push(1);
+                Self::write_blob(&mut out, kb.as_bytes());
+            }
+            None => out.push(0),
+        }
+
+        let all_paths = self.basic_db.get_all_paths();
+        out.extend_from_slice(&(self.composite_path.len() as u32).to_le_bytes());
+        for (kb_name, path) in &self.composite_path {
+            Self::write_blob(&mut out, kb_name.as_bytes());
+            let description = self.kb_descriptions.get(kb_name).cloned().unwrap_or_default();
+            Self::write_blob(&mut out, description.as_bytes());
+
+            out.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            for label in path {
+                Self::write_blob(&mut out, label.as_bytes());
+            }
+
+            let path_values = self.composite_path_values.get(kb_name).cloned().unwrap_or_default();
+            out.extend_from_slice(&(path_values.len() as u32).to_le_bytes());
+            for (node_path, used) in &path_values {
+                Self::write_blob(&mut out, node_path.as_bytes());
+                out.push(if *used { 1 } else { 0 });
+            }
+
+            let kb_prefix = format!("{}.", kb_name);
+            let node_paths: Vec<&String> = all_paths
+                .iter()
+                .filter(|path| path.starts_with(&kb_prefix) || *path == kb_name)
+                .collect();
+            out.extend_from_slice(&(node_paths.len() as u32).to_le_bytes());
+            for node_path in node_paths {
+                let node = self.basic_db.get_node(node_path).ok().flatten();
+                let payload = node.map(|n| serde_json::to_vec(&n).unwrap_or_default()).unwrap_or_default();
+                Self::write_blob(&mut out, node_path.as_bytes());
+                Self::write_blob(&mut out, &payload);
+            }
+        }
+
+        out
+    }
+
+    /// Restores the state written by [`to_bytes`](Self::to_bytes), replacing
+    /// everything currently in `self`. Returns `ConstructMemError::Corruption`
+    /// instead of panicking if `bytes` is truncated or ends with a partial
+    /// record.
+    pub fn from_bytes(&mut self, bytes: &[u8]) -> Result<(), ConstructMemError> {
+        let mut pos = 0usize;
+        if bytes.len() < 6 || &bytes[0..4] != Self::SNAPSHOT_MAGIC.as_slice() {
+            return Err(ConstructMemError::Corruption("bad magic header".to_string()));
+        }
+        pos += 4;
+        let version = u16::from_le_bytes(bytes[pos..pos + 2].try_into().unwrap());
+        pos += 2;
+        if version != Self::SNAPSHOT_VERSION {
+            return Err(ConstructMemError::Corruption(format!("unsupported snapshot version {}", version)));
+        }
+
+        let has_working_kb = Self::read_u8(bytes, &mut pos)?;
+        let working_kb = if has_working_kb == 1 {
+            Some(Self::read_string(bytes, &mut pos)?)
+        } else {
+            None
+        };
+
+        let kb_count = Self::read_u32(bytes, &mut pos)?;
+
+        self.basic_db.clear();
+        self.kb_name = None;
+        self.working_kb = None;
+        self.composite_path.clear();
+        self.composite_path_values.clear();
+        self.path_tries.clear();
+        self.kb_descriptions.clear();
+
+        for _ in 0..kb_count {
+            let kb_name = Self::read_string(bytes, &mut pos)?;
+            let description = Self::read_string(bytes, &mut pos)?;
+
+            let path_len = Self::read_u32(bytes, &mut pos)?;
+            let mut path = Vec::with_capacity(path_len as usize);
+            for _ in 0..path_len {
+                path.push(Self::read_string(bytes, &mut pos)?);
+            }
+
+            let mut path_values = HashMap::new();
+            let mut trie = PathTrie::new();
+            let value_count = Self::read_u32(bytes, &mut pos)?;
+            for _ in 0..value_count {
+                let node_path = Self::read_string(bytes, &mut pos)?;
+                let used = Self::read_u8(bytes, &mut pos)? == 1;
+                if used {
+                    trie.insert(&node_path);
+                }
+                path_values.insert(node_path, used);
+            }
+
+            let node_count = Self::read_u32(bytes, &mut pos)?;
+            for _ in 0..node_count {
+                // The path is also embedded in the serialized `TreeNode`
+                // itself (`restore_node` re-inserts it keyed on that), so the
+                // framed copy here only needs to be skipped over.
+                let _node_path = Self::read_string(bytes, &mut pos)?;
+                let payload = Self::read_blob(bytes, &mut pos)?;
+                let node: TreeNode = serde_json::from_slice(payload)
+                    .map_err(|e| ConstructMemError::Corruption(e.to_string()))?;
+                // `restore_node`, not `store` — `store` always computes a
+                // fresh version against the just-cleared backend, discarding
+                // the version that was actually serialized and silently
+                // resetting every restored node back to version 1.
+                self.basic_db
+                    .restore_node(node)
+                    .map_err(ConstructMemError::Basic)?;
+            }
+
+            self.composite_path.insert(kb_name.clone(), path);
+            self.composite_path_values.insert(kb_name.clone(), path_values);
+            self.path_tries.insert(kb_name.clone(), trie);
+            self.kb_descriptions.insert(kb_name, description);
+        }
+
+        if pos != bytes.len() {
+            return Err(ConstructMemError::Corruption("trailing partial record".to_string()));
+        }
+
+        if let Some(kb) = &working_kb {
+            if !self.composite_path.contains_key(kb) {
+                return Err(ConstructMemError::Corruption(format!("working_kb '{}' has no matching KB record", kb)));
+            }
+        }
+        self.working_kb = working_kb;
+        Ok(())
+    }
+
+    /// Starts a transaction that journals every mutation made through the
+    /// returned handle, so a KB build that fails partway (an
+    /// `AssertionError` in `leave_header_node`, a `PathAlreadyExists` deep in
+    /// a tree, ...) can be cleanly rolled back instead of leaving
+    /// `composite_path`/`composite_path_values`/`basic_db` half-built.
+    /// Nesting is supported via `savepoint`/`rollback_to_savepoint`.
+    pub fn begin_transaction(&mut self) -> ConstructTransaction<'_, B> {
+        ConstructTransaction {
+            db: self,
+            undo_log: Vec::new(),
+        }
+    }
+}
+
+/// The inverse of one mutation recorded by a [`ConstructTransaction`]: the
+/// prior state of whatever `composite_path`/`composite_path_values`/
+/// `basic_db` entry the forward call touched, or a named checkpoint marker
+/// pushed by `savepoint`.
+enum ConstructUndoEntry {
+    /// Restores `composite_path[kb]` to `prior_path` wholesale, the inverse
+    /// of any push/pop made by `add_header_node`/`add_info_node`/
+    /// `leave_header_node`.
+    Path { kb: String, prior_path: Vec<String> },
+    /// Restores (or removes) `composite_path_values[kb][node_path]`, the
+    /// inverse of the "mark path as used" step in `add_header_node`.
+    PathValue { kb: String, node_path: String, prior_value: Option<bool> },
+    /// Restores (or deletes) the `basic_db` node at `path`, the inverse of a
+    /// `store`/`delete` call.
+    BasicNode { path: String, prior_node: Option<TreeNode> },
+    Savepoint(String),
+}
+
+/// A staged batch of `ConstructMemDB` mutations. Every call mirrors the
+/// corresponding `ConstructMemDB` method but first records the prior state
+/// of whatever it's about to touch, so `rollback()` (or
+/// `rollback_to_savepoint()`) can restore `composite_path`,
+/// `composite_path_values`, and `basic_db` together as one unit.
+pub struct ConstructTransaction<'a, B: KbBackend> {
+    db: &'a mut ConstructMemDB<B>,
+    undo_log: Vec<ConstructUndoEntry>,
+}
+
+impl<'a, B: KbBackend> ConstructTransaction<'a, B> {
+    fn record_path(&mut self, kb: &str) {
+        let prior_path = self.db.composite_path.get(kb).cloned().unwrap_or_default();
+        self.undo_log.push(ConstructUndoEntry::Path { kb: kb.to_string(), prior_path });
+    }
+
+    fn record_path_value(&mut self, kb: &str, node_path: &str) {
+        let prior_value = self.db.composite_path_values.get(kb).and_then(|values| values.get(node_path).copied());
+        self.undo_log.push(ConstructUndoEntry::PathValue {
+            kb: kb.to_string(),
+            node_path: node_path.to_string(),
+            prior_value,
+        });
+    }
+
+    fn record_basic_node(&mut self, path: &str) {
+        let prior_node = self.db.basic_db.get_node(path).ok().flatten();
+        self.undo_log.push(ConstructUndoEntry::BasicNode { path: path.to_string(), prior_node });
+    }
+
+    /// Adds a header node, journaling the composite-path push, the
+    /// path-used marker, and the `basic_db` store it performs.
+    pub fn add_header_node(
+        &mut self,
+        link: String,
+        node_name: String,
+        node_data: HashMap<String, Value>,
+        description: Option<String>,
+    ) -> Result<(), ConstructMemError> {
+        let working_kb = self.db.working_kb.as_ref().ok_or(ConstructMemError::NoWorkingKB)?.clone();
+        self.record_path(&working_kb);
+
+        let node_path = self
+            .db
+            .composite_path
+            .get(&working_kb)
+            .map(|path| {
+                let mut joined = path.clone();
+                joined.push(link.clone());
+                joined.push(node_name.clone());
+                joined.join(".")
+            })
+            .unwrap_or_default();
+        self.record_path_value(&working_kb, &node_path);
+        self.record_basic_node(&node_path);
+
+        self.db.add_header_node(link, node_name, node_data, description)
+    }
+
+    /// Adds an info node, journaling the same steps as `add_header_node`
+    /// plus the composite-path pop it performs afterwards.
+    pub fn add_info_node(
+        &mut self,
+        link: String,
+        node_name: String,
+        node_data: HashMap<String, Value>,
+        description: Option<String>,
+    ) -> Result<(), ConstructMemError> {
+        let working_kb = self.db.working_kb.as_ref().ok_or(ConstructMemError::NoWorkingKB)?.clone();
+        self.record_path(&working_kb);
+
+        let node_path = self
+            .db
+            .composite_path
+            .get(&working_kb)
+            .map(|path| {
+                let mut joined = path.clone();
+                joined.push(link.clone());
+                joined.push(node_name.clone());
+                joined.join(".")
+            })
+            .unwrap_or_default();
+        self.record_path_value(&working_kb, &node_path);
+        self.record_basic_node(&node_path);
+
+        self.db.add_info_node(link, node_name, node_data, description)
+    }
+
+    /// Leaves a header node, journaling the composite-path pop so a failed
+    /// assertion (wrong label/name) can still be rolled back consistently
+    /// with everything else in the transaction.
+    pub fn leave_header_node(&mut self, label: String, name: String) -> Result<(), ConstructMemError> {
+        if let Some(working_kb) = self.db.working_kb.clone() {
+            self.record_path(&working_kb);
+        }
+        self.db.leave_header_node(label, name)
+    }
+
+    /// Stores directly into `basic_db`, journaling the prior node state.
+    pub fn store(
+        &mut self,
+        path: &str,
+        data: Value,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+    ) -> Result<u64, KbError> {
+        self.record_basic_node(path);
+        self.db.basic_db.store(path, data, created_at, updated_at)
+    }
+
+    /// Deletes directly from `basic_db`, journaling the prior node state.
+    pub fn delete(&mut self, path: &str) -> bool {
+        self.record_basic_node(path);
+        self.db.basic_db.delete(path)
+    }
+
+    /// Pushes a named marker into the undo log that `rollback_to_savepoint`
+    /// and `release_savepoint` can refer back to, supporting nested
+    /// transactions by rolling back only to an inner checkpoint.
+    pub fn savepoint(&mut self, name: &str) {
+        self.undo_log.push(ConstructUndoEntry::Savepoint(name.to_string()));
+    }
+
+    fn apply_undo(&mut self, entry: ConstructUndoEntry) {
+        match entry {
+            ConstructUndoEntry::Path { kb, prior_path } => {
+                self.db.composite_path.insert(kb, prior_path);
+            }
+            ConstructUndoEntry::PathValue { kb, node_path, prior_value } => {
+                let values = self.db.composite_path_values.entry(kb.clone()).or_default();
+                match prior_value {
+                    Some(value) => {
+                        values.insert(node_path.clone(), value);
+                        self.db.path_tries.entry(kb).or_default().insert(&node_path);
+                    }
+                    None => {
+                        values.remove(&node_path);
+                        if let Some(trie) = self.db.path_tries.get_mut(&kb) {
+                            trie.remove(&node_path);
+                        }
+                    }
+                }
+            }
+            ConstructUndoEntry::BasicNode { path, prior_node } => match prior_node {
+                Some(node) => {
+                    // `restore_node`, not `store` — `store` always bumps the
+                    // version past what it was before the transaction, which
+                    // would corrupt the CAS invariant `compare_and_store`/
+                    // `get_versioned` rely on for every caller that rolls back.
+                    let _ = self.db.basic_db.restore_node(node);
+                }
+                None => {
+                    self.db.basic_db.delete(&path);
+                }
+            },
+            ConstructUndoEntry::Savepoint(_) => {}
+        }
+    }
+
+    /// Undoes every mutation recorded since `savepoint(name)`, leaving the
+    /// savepoint itself in place so it can be rolled back to again.
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> Result<(), ConstructMemError> {
+        let pos = self
+            .undo_log
+            .iter()
+            .rposition(|entry| matches!(entry, ConstructUndoEntry::Savepoint(marker) if marker == name))
+            .ok_or_else(|| ConstructMemError::AssertionError(format!("No savepoint named '{}'", name)))?;
+
+        while self.undo_log.len() > pos + 1 {
+            let entry = self.undo_log.pop().unwrap();
+            self.apply_undo(entry);
+        }
+        Ok(())
+    }
+
+    /// Forgets a savepoint without undoing anything, the way a nested
+    /// transaction's changes get folded into its parent on success.
+    pub fn release_savepoint(&mut self, name: &str) -> Result<(), ConstructMemError> {
+        let pos = self
+            .undo_log
+            .iter()
+            .rposition(|entry| matches!(entry, ConstructUndoEntry::Savepoint(marker) if marker == name))
+            .ok_or_else(|| ConstructMemError::AssertionError(format!("No savepoint named '{}'", name)))?;
+        self.undo_log.remove(pos);
+        Ok(())
+    }
+
+    /// Keeps every staged mutation. The tree already reflects them, so this
+    /// simply discards the undo log.
+    pub fn commit(self) {}
+
+    /// Restores every path, path-used marker, and `basic_db` node touched
+    /// since `begin_transaction()`, undoing the transaction as a whole.
+    pub fn rollback(mut self) {
+        while let Some(entry) = self.undo_log.pop() {
+            self.apply_undo(entry);
+        }
+    }
 }
 
 // Implement Default for convenience
-impl Default for ConstructMemDB {
+impl Default for ConstructMemDB<BasicConstructDB> {
     fn default() -> Self {
         Self::new(
             "localhost".to_string(),
@@ -603,5 +1154,177 @@ mod tests {
         assert!(paths.contains(&"test_kb.link1.node1".to_string()));
         assert!(paths.contains(&"test_kb.link2.node2".to_string()));
     }
+
+    #[test]
+    fn test_to_bytes_from_bytes_round_trip() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut node_data = HashMap::new();
+        node_data.insert("key".to_string(), json!("value1"));
+        db.add_header_node("link1".to_string(), "node1".to_string(), node_data, None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+
+        let bytes = db.to_bytes();
+
+        let mut restored = ConstructMemDB::default();
+        restored.from_bytes(&bytes).unwrap();
+
+        assert_eq!(restored.get_all_kb_names(), vec!["test_kb"]);
+        assert_eq!(restored.get_working_kb(), Some(&"test_kb".to_string()));
+        let stats = restored.get_kb_stats("test_kb").unwrap();
+        assert_eq!(stats.0, 1);
+        let node = restored.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap();
+        assert_eq!(node.data, json!({"key": "value1"}));
+    }
+
+    #[test]
+    fn test_to_bytes_from_bytes_preserves_version() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut node_data = HashMap::new();
+        node_data.insert("key".to_string(), json!("value1"));
+        db.add_header_node("link1".to_string(), "node1".to_string(), node_data, None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+        // Store over the node once more so its version is past the `1` a
+        // fresh `store()` call would assign on restore.
+        db.basic_db_mut().store("test_kb.link1.node1", json!({"key": "value2"}), None, None).unwrap();
+        let version_before = db.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap().version;
+        assert!(version_before > 1);
+
+        let bytes = db.to_bytes();
+        let mut restored = ConstructMemDB::default();
+        restored.from_bytes(&bytes).unwrap();
+
+        let node = restored.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap();
+        assert_eq!(node.version, version_before);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_truncated_buffer() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+
+        let bytes = db.to_bytes();
+        let truncated = &bytes[..bytes.len() - 1];
+
+        let mut restored = ConstructMemDB::default();
+        let err = restored.from_bytes(truncated).unwrap_err();
+        assert!(matches!(err, ConstructMemError::Corruption(_)));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_bad_magic() {
+        let mut restored = ConstructMemDB::default();
+        let err = restored.from_bytes(b"not a snapshot at all").unwrap_err();
+        assert!(matches!(err, ConstructMemError::Corruption(_)));
+    }
+
+    #[test]
+    fn test_add_or_merge_header_node_reject_matches_add_header_node() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut node_data = HashMap::new();
+        node_data.insert("key".to_string(), json!("value1"));
+        db.add_header_node("link1".to_string(), "node1".to_string(), node_data.clone(), None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+
+        let err = db
+            .add_or_merge_header_node("link1".to_string(), "node1".to_string(), node_data, None, MergePolicy::Reject)
+            .unwrap_err();
+        assert!(matches!(err, ConstructMemError::PathAlreadyExists(_)));
+    }
+
+    #[test]
+    fn test_add_or_merge_header_node_replace() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut first = HashMap::new();
+        first.insert("key".to_string(), json!("value1"));
+        first.insert("stale".to_string(), json!("gone"));
+        db.add_header_node("link1".to_string(), "node1".to_string(), first, None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+
+        let mut second = HashMap::new();
+        second.insert("key".to_string(), json!("value2"));
+        db.add_or_merge_header_node("link1".to_string(), "node1".to_string(), second, None, MergePolicy::Replace).unwrap();
+
+        let stored = db.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap();
+        assert_eq!(stored.data, json!({"key": "value2"}));
+    }
+
+    #[test]
+    fn test_add_or_merge_header_node_deep_merge() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut first = HashMap::new();
+        first.insert("a".to_string(), json!(1));
+        first.insert("nested".to_string(), json!({"x": 1, "y": 2}));
+        db.add_header_node("link1".to_string(), "node1".to_string(), first, None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+
+        let mut second = HashMap::new();
+        second.insert("b".to_string(), json!(2));
+        second.insert("nested".to_string(), json!({"y": 20, "z": 3}));
+        db.add_or_merge_header_node("link1".to_string(), "node1".to_string(), second, None, MergePolicy::DeepMerge).unwrap();
+
+        let stored = db.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap();
+        assert_eq!(stored.data, json!({"a": 1, "b": 2, "nested": {"x": 1, "y": 20, "z": 3}}));
+    }
+
+    #[test]
+    fn test_add_or_merge_header_node_custom() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut first = HashMap::new();
+        first.insert("count".to_string(), json!(1));
+        db.add_header_node("link1".to_string(), "node1".to_string(), first, None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+
+        let mut second = HashMap::new();
+        second.insert("count".to_string(), json!(1));
+        let sum_counts: MergePolicy = MergePolicy::Custom(Box::new(|existing, incoming| {
+            let existing_count = existing.get("count").and_then(|v| v.as_i64()).unwrap_or(0);
+            let incoming_count = incoming.get("count").and_then(|v| v.as_i64()).unwrap_or(0);
+            json!({"count": existing_count + incoming_count})
+        }));
+        db.add_or_merge_header_node("link1".to_string(), "node1".to_string(), second, None, sum_counts).unwrap();
+
+        let stored = db.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap();
+        assert_eq!(stored.data, json!({"count": 2}));
+    }
+
+    #[test]
+    fn test_rollback_restores_version() {
+        let mut db = ConstructMemDB::default();
+        db.add_kb("test_kb".to_string(), "Test KB".to_string()).unwrap();
+        db.select_kb("test_kb".to_string()).unwrap();
+
+        let mut node_data = HashMap::new();
+        node_data.insert("key".to_string(), json!("value1"));
+        db.add_header_node("link1".to_string(), "node1".to_string(), node_data, None).unwrap();
+        db.leave_header_node("link1".to_string(), "node1".to_string()).unwrap();
+        let version_before = db.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap().version;
+
+        let mut txn = db.begin_transaction();
+        txn.store("test_kb.link1.node1", json!({"key": "value2"}), None, None).unwrap();
+        txn.store("test_kb.link1.node1", json!({"key": "value3"}), None, None).unwrap();
+        txn.rollback();
+
+        let node = db.basic_db().get_node("test_kb.link1.node1").unwrap().unwrap();
+        assert_eq!(node.data, json!({"key": "value1"}));
+        assert_eq!(node.version, version_before);
+    }
 }
 
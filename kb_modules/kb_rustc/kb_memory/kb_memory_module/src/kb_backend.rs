@@ -0,0 +1,140 @@
+use serde_json::Value;
+
+use crate::basic_memory_module::{BasicConstructDB, KbError, TreeNode};
+
+/// The node-storage operations `ConstructMemDB` actually relies on, factored
+/// out so it can be built generically over any key/value store — the
+/// in-memory `BasicConstructDB` by default, or a durable backend — without
+/// touching composite-path tracking, which stays entirely inside
+/// `ConstructMemDB` itself. Mirrors [`StorageBackend`](crate::storage_backend::StorageBackend)'s
+/// role as an extension point, but scoped to exactly the calls `ConstructMemDB`
+/// and its transaction journal make rather than `BasicConstructDB`'s full
+/// query surface.
+pub trait KbBackend {
+    /// Stores `data` at `path`, returning its new version number.
+    fn store(&mut self, path: &str, data: Value, created_at: Option<String>, updated_at: Option<String>) -> Result<u64, KbError>;
+
+    /// Removes the node at `path`, returning whether it existed.
+    fn delete(&mut self, path: &str) -> bool;
+
+    /// Returns every stored path.
+    fn get_all_paths(&self) -> Vec<String>;
+
+    /// Removes every stored node.
+    fn clear(&mut self);
+
+    /// Reads the full node at `path` (not just its `data`), so a
+    /// transaction's undo log can restore `created_at`/`updated_at` exactly.
+    fn get_node(&self, path: &str) -> Result<Option<TreeNode>, KbError>;
+
+    /// Re-inserts `node` exactly as given, preserving its `version` rather
+    /// than bumping it the way `store` does. `store` always computes
+    /// `version = current_stored_version + 1`, which is correct for a fresh
+    /// write but corrupts the CAS invariant `compare_and_store`/
+    /// `get_versioned` rely on when the caller is restoring a node to a
+    /// state it was already in — a transaction rollback or a snapshot
+    /// reload — rather than making a new write.
+    fn restore_node(&mut self, node: TreeNode) -> Result<(), KbError>;
+}
+
+impl KbBackend for BasicConstructDB {
+    fn store(&mut self, path: &str, data: Value, created_at: Option<String>, updated_at: Option<String>) -> Result<u64, KbError> {
+        BasicConstructDB::store(self, path, data, created_at, updated_at)
+    }
+
+    fn delete(&mut self, path: &str) -> bool {
+        BasicConstructDB::delete(self, path)
+    }
+
+    fn get_all_paths(&self) -> Vec<String> {
+        BasicConstructDB::get_all_paths(self)
+    }
+
+    fn clear(&mut self) {
+        BasicConstructDB::clear(self)
+    }
+
+    fn get_node(&self, path: &str) -> Result<Option<TreeNode>, KbError> {
+        BasicConstructDB::get_node(self, path)
+    }
+
+    fn restore_node(&mut self, node: TreeNode) -> Result<(), KbError> {
+        BasicConstructDB::restore_node(self, node)
+    }
+}
+
+/// Durable `KbBackend` on top of `sled`, the same embedded store
+/// [`SledBackend`](crate::storage_backend::SledBackend) already uses for
+/// `BasicConstructDB`'s optional mirror sync — reused here rather than
+/// pulling in a new RocksDB/LMDB dependency, so a `ConstructMemDB` can persist
+/// node data to disk with no Postgres connection required. Composite-path
+/// tracking stays in `ConstructMemDB` itself; only node data round-trips
+/// through this backend.
+pub struct SledKbBackend {
+    tree: sled::Tree,
+}
+
+impl SledKbBackend {
+    /// Opens (or creates) a sled database at `path` on disk.
+    pub fn open(path: &str) -> Result<Self, KbError> {
+        let db = sled::open(path).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        let tree = db
+            .open_tree("kb_nodes")
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        Ok(Self { tree })
+    }
+
+    fn decode(bytes: &[u8]) -> Result<TreeNode, KbError> {
+        serde_json::from_slice(bytes).map_err(|e| KbError::DatabaseError(e.to_string()))
+    }
+}
+
+impl KbBackend for SledKbBackend {
+    fn store(&mut self, path: &str, data: Value, created_at: Option<String>, updated_at: Option<String>) -> Result<u64, KbError> {
+        let prior_version = self.get_node(path)?.map(|node| node.version).unwrap_or(0);
+        let node = TreeNode {
+            path: path.to_string(),
+            data,
+            created_at,
+            updated_at,
+            version: prior_version + 1,
+        };
+        let bytes = serde_json::to_vec(&node).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        self.tree
+            .insert(path.as_bytes(), bytes)
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        Ok(node.version)
+    }
+
+    fn delete(&mut self, path: &str) -> bool {
+        self.tree.remove(path.as_bytes()).map(|removed| removed.is_some()).unwrap_or(false)
+    }
+
+    fn get_all_paths(&self) -> Vec<String> {
+        self.tree
+            .iter()
+            .keys()
+            .filter_map(|res| res.ok())
+            .map(|key| String::from_utf8_lossy(&key).to_string())
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        let _ = self.tree.clear();
+    }
+
+    fn get_node(&self, path: &str) -> Result<Option<TreeNode>, KbError> {
+        match self.tree.get(path.as_bytes()).map_err(|e| KbError::DatabaseError(e.to_string()))? {
+            Some(bytes) => Ok(Some(Self::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn restore_node(&mut self, node: TreeNode) -> Result<(), KbError> {
+        let bytes = serde_json::to_vec(&node).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        self.tree
+            .insert(node.path.as_bytes(), bytes)
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+/// One label segment of a dot-separated path. `terminal` marks that the
+/// path ending at this node was actually inserted (as opposed to merely
+/// being an ancestor of something that was), the same distinction a radix
+/// trie over filesystem paths makes between a directory and a file.
+#[derive(Default)]
+struct TrieNode {
+    terminal: bool,
+    children: HashMap<String, TrieNode>,
+}
+
+/// A radix/Patricia trie over dot-segmented ltree-style labels, backing
+/// `ConstructMemDB`'s `descendants`/`ancestors`/`match_paths` so a subtree
+/// query only walks the relevant branch instead of scanning every stored
+/// path.
+#[derive(Default)]
+pub struct PathTrie {
+    root: TrieNode,
+}
+
+impl PathTrie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `path` as present, creating any missing intermediate labels.
+    pub fn insert(&mut self, path: &str) {
+        let mut node = &mut self.root;
+        for label in path.split('.') {
+            node = node.children.entry(label.to_string()).or_default();
+        }
+        node.terminal = true;
+    }
+
+    /// Unmarks `path` as present. Intermediate labels are left in place
+    /// (they may still be prefixes of other stored paths); this only clears
+    /// the terminal flag, the same lazy-cleanup tradeoff `basic_db`'s own
+    /// tombstone set makes instead of eagerly pruning.
+    pub fn remove(&mut self, path: &str) {
+        let mut node = &mut self.root;
+        for label in path.split('.') {
+            match node.children.get_mut(label) {
+                Some(child) => node = child,
+                None => return,
+            }
+        }
+        node.terminal = false;
+    }
+
+    fn join(prefix: &str, label: &str) -> String {
+        if prefix.is_empty() {
+            label.to_string()
+        } else {
+            format!("{}.{}", prefix, label)
+        }
+    }
+
+    fn node_at(&self, prefix: &str) -> Option<&TrieNode> {
+        if prefix.is_empty() {
+            return Some(&self.root);
+        }
+        let mut node = &self.root;
+        for label in prefix.split('.') {
+            node = node.children.get(label)?;
+        }
+        Some(node)
+    }
+
+    fn collect(node: &TrieNode, prefix: &str, out: &mut Vec<String>) {
+        if node.terminal && !prefix.is_empty() {
+            out.push(prefix.to_string());
+        }
+        for (label, child) in &node.children {
+            Self::collect(child, &Self::join(prefix, label), out);
+        }
+    }
+
+    /// Every stored path at or under `prefix` (an `ltree @>` query), found
+    /// by walking only `prefix`'s branch instead of scanning every path.
+    /// An empty `prefix` returns every stored path in the trie.
+    pub fn descendants(&self, prefix: &str) -> Vec<String> {
+        let Some(node) = self.node_at(prefix) else {
+            return Vec::new();
+        };
+        let mut out = Vec::new();
+        Self::collect(node, prefix, &mut out);
+        out.sort();
+        out
+    }
+
+    /// Every stored path that is a strict ancestor of `path` (an `ltree <@`
+    /// query run upward), ordered from shallowest to deepest.
+    pub fn ancestors(&self, path: &str) -> Vec<String> {
+        let labels: Vec<&str> = path.split('.').collect();
+        let mut out = Vec::new();
+        let mut node = &self.root;
+        let mut acc = String::new();
+        for (i, label) in labels.iter().enumerate() {
+            let Some(child) = node.children.get(*label) else {
+                break;
+            };
+            node = child;
+            acc = Self::join(&acc, label);
+            if node.terminal && i + 1 < labels.len() {
+                out.push(acc.clone());
+            }
+        }
+        out
+    }
+
+    fn expand_double_star<'a>(node: &'a TrieNode, path: &str, out: &mut Vec<(&'a TrieNode, String)>) {
+        out.push((node, path.to_string()));
+        for (label, child) in &node.children {
+            Self::expand_double_star(child, &Self::join(path, label), out);
+        }
+    }
+
+    /// `lquery`-style pattern match: `*` matches exactly one label, `**`
+    /// matches zero or more labels, anything else must match a label
+    /// literally. A frontier of `(trie node, path so far)` candidates is
+    /// advanced one pattern segment at a time, so a literal segment only
+    /// ever looks up its one matching child instead of re-scanning.
+    pub fn match_paths(&self, pattern: &str) -> Vec<String> {
+        if pattern.is_empty() {
+            return if self.root.terminal { vec![String::new()] } else { Vec::new() };
+        }
+
+        let mut frontier: Vec<(&TrieNode, String)> = vec![(&self.root, String::new())];
+        for segment in pattern.split('.') {
+            let mut next = Vec::new();
+            match segment {
+                "*" => {
+                    for (node, path) in &frontier {
+                        for (label, child) in &node.children {
+                            next.push((child, Self::join(path, label)));
+                        }
+                    }
+                }
+                "**" => {
+                    for (node, path) in &frontier {
+                        Self::expand_double_star(node, path, &mut next);
+                    }
+                }
+                literal => {
+                    for (node, path) in &frontier {
+                        if let Some(child) = node.children.get(literal) {
+                            next.push((child, Self::join(path, literal)));
+                        }
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        let mut out: Vec<String> = frontier
+            .into_iter()
+            .filter(|(node, _)| node.terminal)
+            .map(|(_, path)| path)
+            .collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PathTrie {
+        let mut trie = PathTrie::new();
+        for path in ["a.b.node1", "a.c.node1", "a.b.node2", "a"] {
+            trie.insert(path);
+        }
+        trie
+    }
+
+    #[test]
+    fn test_descendants() {
+        let trie = sample();
+        let mut under_a_b = trie.descendants("a.b");
+        under_a_b.sort();
+        assert_eq!(under_a_b, vec!["a.b.node1", "a.b.node2"]);
+
+        assert_eq!(trie.descendants("a.zzz"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let trie = sample();
+        assert_eq!(trie.ancestors("a.b.node1"), vec!["a".to_string()]);
+        assert_eq!(trie.ancestors("a"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_match_paths_star_and_double_star() {
+        let trie = sample();
+
+        let mut single = trie.match_paths("a.*.node1");
+        single.sort();
+        assert_eq!(single, vec!["a.b.node1", "a.c.node1"]);
+
+        let mut any_depth = trie.match_paths("a.**");
+        any_depth.sort();
+        assert_eq!(any_depth, vec!["a", "a.b.node1", "a.b.node2", "a.c.node1"]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut trie = sample();
+        trie.remove("a.b.node1");
+        assert_eq!(trie.descendants("a.b"), vec!["a.b.node2".to_string()]);
+    }
+}
@@ -1,23 +1,148 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use serde_json::Value;
 use crate::basic_memory_module::{BasicConstructDB, KbError, TreeNode};
 
+/// Closure backing a registered secondary-index view: given a path and its
+/// node, emits zero or more `(index key, optional payload)` pairs to index
+/// that node under.
+pub type ViewMapFn = Box<dyn Fn(&str, &TreeNode) -> Vec<(String, Option<Value>)>>;
+
+/// A single filter step deferred by `SearchChain`'s builder methods, applied
+/// only once `results()`/`result_keys()`/`explain()` has cost-reordered them.
+#[derive(Debug, Clone)]
+enum Predicate {
+    Kb(String),
+    Label(String),
+    Name(String),
+    PropertyKey(String),
+    PropertyValue(String, Value),
+    Path(String, String),
+}
+
+impl Predicate {
+    fn describe(&self) -> String {
+        match self {
+            Predicate::Kb(kb) => format!("kb({kb})"),
+            Predicate::Label(label) => format!("label({label})"),
+            Predicate::Name(name) => format!("name({name})"),
+            Predicate::PropertyKey(key) => format!("property_key({key})"),
+            Predicate::PropertyValue(key, value) => format!("property_value({key}, {value})"),
+            Predicate::Path(operator, starting_path) => format!("path({operator}, {starting_path})"),
+        }
+    }
+}
+
+/// One predicate's place in a `SearchChain`'s cost-based order, with its
+/// estimated (pre-execution) and actual (post-execution) surviving-row counts.
+#[derive(Debug, Clone)]
+pub struct PredicateExplain {
+    pub predicate: String,
+    pub estimated_cardinality: usize,
+    pub actual_cardinality: usize,
+}
+
+/// A compact, growable set of `u32` doc-ids backed by 64-bit words. Chained
+/// `search_*` filters intersect these instead of cloning `TreeNode`s at every
+/// step, so only the final step of a chain pays for materializing results.
+#[derive(Debug, Clone, Default)]
+struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    /// A bitmap with every bit in `0..n` set, i.e. "everything matches".
+    fn full(n: u32) -> Self {
+        let mut bitmap = Self::default();
+        for id in 0..n {
+            bitmap.set(id);
+        }
+        bitmap
+    }
+
+    fn set(&mut self, id: u32) {
+        let word = id as usize / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1u64 << (id % 64);
+    }
+
+    fn get(&self, id: u32) -> bool {
+        self.words
+            .get(id as usize / 64)
+            .map(|w| w & (1u64 << (id % 64)) != 0)
+            .unwrap_or(false)
+    }
+
+    fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Intersects `self` and `other`, returning a new bitmap.
+    fn and(&self, other: &Bitmap) -> Bitmap {
+        let len = self.words.len().min(other.words.len());
+        Bitmap {
+            words: (0..len).map(|i| self.words[i] & other.words[i]).collect(),
+        }
+    }
+
+    /// Iterates the set doc-ids in ascending order.
+    fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().enumerate().flat_map(|(word_idx, &word)| {
+            (0..64u32)
+                .filter(move |bit| word & (1u64 << bit) != 0)
+                .map(move |bit| word_idx as u32 * 64 + bit)
+        })
+    }
+}
+
 /// SearchMemDB extends BasicConstructDB with search and filtering capabilities
 pub struct SearchMemDB {
     /// Embedded BasicConstructDB for inheritance-like behavior
     pub basic_db: BasicConstructDB,
     /// Generated decoded keys
     keys: HashMap<String, Vec<String>>,
-    /// Knowledge bases mapping
-    kbs: HashMap<String, Vec<String>>,
-    /// Labels mapping
-    labels: HashMap<String, Vec<String>>,
-    /// Names mapping
-    names: HashMap<String, Vec<String>>,
+    /// Knowledge bases mapping: kb name -> bitmap of doc-ids belonging to it
+    kbs: HashMap<String, Bitmap>,
+    /// Labels mapping: label -> bitmap of doc-ids carrying it
+    labels: HashMap<String, Bitmap>,
+    /// Names mapping: name -> bitmap of doc-ids carrying it
+    names: HashMap<String, Bitmap>,
     /// Decoded path keys
     pub decoded_keys: HashMap<String, Vec<String>>,
-    /// Current filter results
+    /// Stable doc-id -> path table, indexed by the `u32` assigned in
+    /// `generate_decoded_keys`
+    id_to_path: Vec<String>,
+    /// Reverse lookup from path to its doc-id
+    path_to_id: HashMap<String, u32>,
+    /// The current filter state as a bitmap of surviving doc-ids. This is the
+    /// source of truth for filtering; `filter_results` is a materialized view
+    /// of it.
+    filter_bitmap: Bitmap,
+    /// Materialized view of `filter_bitmap`, resolved to full nodes. Kept in
+    /// sync after any standalone `search_*`/`clear_filters` call and after
+    /// `SearchChain::results()`/`result_keys()`, but deliberately NOT after
+    /// every step of a chain, so chained searches intersect bitmaps without
+    /// cloning nodes at each step.
     pub filter_results: HashMap<String, TreeNode>,
+    /// Inverted index over node descriptions and string fields: lowercased
+    /// token -> (path -> term frequency within that node's text)
+    text_index: HashMap<String, HashMap<String, usize>>,
+    /// Total tokenized text length per path, used as the document length in
+    /// BM25 scoring
+    doc_lengths: HashMap<String, usize>,
+    /// Closures for user-registered secondary-index views, keyed by view name
+    view_fns: HashMap<String, ViewMapFn>,
+    /// Postings for each registered view: view name -> index key ->
+    /// accumulated `{"path": .., "payload": ..}` entries for that key
+    views: HashMap<String, BTreeMap<String, Value>>,
+    /// Which index keys each path currently contributes to a given view, so
+    /// incremental updates can retract stale postings before re-adding them
+    view_contributions: HashMap<String, HashMap<String, Vec<String>>>,
 }
 
 #[derive(Debug)]
@@ -71,7 +196,15 @@ impl SearchMemDB {
             labels: HashMap::new(),
             names: HashMap::new(),
             decoded_keys: HashMap::new(),
+            id_to_path: Vec::new(),
+            path_to_id: HashMap::new(),
+            filter_bitmap: Bitmap::default(),
             filter_results: HashMap::new(),
+            text_index: HashMap::new(),
+            doc_lengths: HashMap::new(),
+            view_fns: HashMap::new(),
+            views: HashMap::new(),
+            view_contributions: HashMap::new(),
         };
 
         // Generate decoded keys
@@ -103,7 +236,15 @@ impl SearchMemDB {
             labels: HashMap::new(),
             names: HashMap::new(),
             decoded_keys: HashMap::new(),
+            id_to_path: Vec::new(),
+            path_to_id: HashMap::new(),
+            filter_bitmap: Bitmap::default(),
             filter_results: HashMap::new(),
+            text_index: HashMap::new(),
+            doc_lengths: HashMap::new(),
+            view_fns: HashMap::new(),
+            views: HashMap::new(),
+            view_contributions: HashMap::new(),
         };
 
         smdb.generate_decoded_keys();
@@ -117,17 +258,28 @@ impl SearchMemDB {
         self.labels.clear();
         self.names.clear();
         self.decoded_keys.clear();
+        self.text_index.clear();
+        self.doc_lengths.clear();
+        self.id_to_path.clear();
+        self.path_to_id.clear();
 
-        // Get all paths from the basic DB
+        // Get all paths from the basic DB and assign each a stable doc-id
         let all_paths = self.basic_db.get_all_paths();
-        
-        for key in all_paths {
+        for path in &all_paths {
+            let id = self.id_to_path.len() as u32;
+            self.id_to_path.push(path.clone());
+            self.path_to_id.insert(path.clone(), id);
+        }
+
+        for key in &all_paths {
             // Skip if we can't get the node
-            if let Ok(Some(_node)) = self.basic_db.get_node(&key) {
+            if let Ok(Some(node)) = self.basic_db.get_node(key) {
+                self.index_text_for(key, &node);
+
                 // Split the key into components
                 let components: Vec<String> = key.split('.').map(|s| s.to_string()).collect();
                 self.decoded_keys.insert(key.clone(), components.clone());
-                
+
                 if components.len() < 3 {
                     // Skip keys that don't have at least kb.label.name structure
                     continue;
@@ -136,147 +288,339 @@ impl SearchMemDB {
                 let kb = &components[0];
                 let label = &components[components.len() - 2];
                 let name = &components[components.len() - 1];
+                let id = self.path_to_id[key];
 
                 // Add to knowledge bases map
-                self.kbs.entry(kb.clone()).or_insert_with(Vec::new).push(key.clone());
+                self.kbs.entry(kb.clone()).or_insert_with(Bitmap::default).set(id);
 
                 // Add to labels map
-                self.labels.entry(label.clone()).or_insert_with(Vec::new).push(key.clone());
+                self.labels.entry(label.clone()).or_insert_with(Bitmap::default).set(id);
 
                 // Add to names map
-                self.names.entry(name.clone()).or_insert_with(Vec::new).push(key.clone());
+                self.names.entry(name.clone()).or_insert_with(Bitmap::default).set(id);
             }
         }
 
         self.keys = self.decoded_keys.clone();
     }
 
-    /// Clears all filters and resets the query state
-    #[allow(unused_variables)]
-    pub fn clear_filters(&mut self) {
-        self.filter_results.clear();
-        
-        // Copy all data to filter results
-        let all_paths = self.basic_db.get_all_paths();
-        for key in all_paths {
-            if let Ok(Some(node)) = self.basic_db.get_node(&key) {
-                self.filter_results.insert(key, node);
+    /// Resolves the current `filter_bitmap` into `filter_results`, cloning
+    /// each surviving node exactly once.
+    fn materialize_filter_results(&mut self) {
+        let mut results = HashMap::new();
+        for id in self.filter_bitmap.iter() {
+            let path = &self.id_to_path[id as usize];
+            if let Ok(Some(node)) = self.basic_db.get_node(path) {
+                results.insert(path.clone(), node);
             }
         }
+        self.filter_results = results;
     }
 
-    /// Searches for rows matching the specified knowledge base
-    pub fn search_kb(&mut self, knowledge_base: &str) -> &HashMap<String, TreeNode> {
-        let mut new_filter_results = HashMap::new();
-        
-        if let Some(kb_keys) = self.kbs.get(knowledge_base) {
-            for key in kb_keys {
-                if let Some(node) = self.filter_results.get(key) {
-                    new_filter_results.insert(key.clone(), node.clone());
+    /// Intersects `filter_bitmap` with the doc-ids indexed under `kb` in `kbs`.
+    fn filter_kb_bitmap(&mut self, knowledge_base: &str) {
+        let index_bitmap = self.kbs.get(knowledge_base).cloned().unwrap_or_default();
+        self.filter_bitmap = self.filter_bitmap.and(&index_bitmap);
+    }
+
+    /// Intersects `filter_bitmap` with the doc-ids indexed under `label` in `labels`.
+    fn filter_label_bitmap(&mut self, label: &str) {
+        let index_bitmap = self.labels.get(label).cloned().unwrap_or_default();
+        self.filter_bitmap = self.filter_bitmap.and(&index_bitmap);
+    }
+
+    /// Intersects `filter_bitmap` with the doc-ids indexed under `name` in `names`.
+    fn filter_name_bitmap(&mut self, name: &str) {
+        let index_bitmap = self.names.get(name).cloned().unwrap_or_default();
+        self.filter_bitmap = self.filter_bitmap.and(&index_bitmap);
+    }
+
+    /// Intersects `filter_bitmap` with the doc-ids of `paths`, for filters
+    /// backed by an ad-hoc path list (ltree operators, views) rather than a
+    /// precomputed index.
+    fn filter_path_bitmap(&mut self, paths: &[String]) {
+        let mut bitmap = Bitmap::default();
+        for path in paths {
+            if let Some(&id) = self.path_to_id.get(path) {
+                bitmap.set(id);
+            }
+        }
+        self.filter_bitmap = self.filter_bitmap.and(&bitmap);
+    }
+
+    /// Replaces `filter_bitmap` with the subset of its currently-set doc-ids
+    /// whose node satisfies `predicate`. Since there's no index for arbitrary
+    /// data-field predicates, this only iterates doc-ids already in the
+    /// current filter, never the whole tree.
+    fn filter_property_bitmap(&mut self, predicate: impl Fn(&TreeNode) -> bool) {
+        let mut bitmap = Bitmap::default();
+        for id in self.filter_bitmap.iter() {
+            let path = &self.id_to_path[id as usize];
+            if let Ok(Some(node)) = self.basic_db.get_node(path) {
+                if predicate(&node) {
+                    bitmap.set(id);
                 }
             }
         }
-        
-        self.filter_results = new_filter_results;
+        self.filter_bitmap = bitmap;
+    }
+
+    /// Estimates how many rows a `SearchChain` predicate would leave standing,
+    /// from statistics already on hand: index-backed predicates (`kb`/`label`/
+    /// `name`) use their posting-list length; un-indexed predicates (property
+    /// scans, path expressions) get a fixed high estimate since there's no
+    /// cheap way to know their selectivity without running them.
+    fn estimate_predicate_cardinality(&self, predicate: &Predicate) -> usize {
+        match predicate {
+            Predicate::Kb(kb) => self.kbs.get(kb).map(Bitmap::len).unwrap_or(0),
+            Predicate::Label(label) => self.labels.get(label).map(Bitmap::len).unwrap_or(0),
+            Predicate::Name(name) => self.names.get(name).map(Bitmap::len).unwrap_or(0),
+            Predicate::PropertyKey(_) | Predicate::PropertyValue(_, _) | Predicate::Path(_, _) => {
+                self.id_to_path.len()
+            }
+        }
+    }
+
+    /// Applies one already-ordered `SearchChain` predicate to `filter_bitmap`.
+    fn apply_predicate(&mut self, predicate: &Predicate) {
+        match predicate {
+            Predicate::Kb(kb) => self.filter_kb_bitmap(kb),
+            Predicate::Label(label) => self.filter_label_bitmap(label),
+            Predicate::Name(name) => self.filter_name_bitmap(name),
+            Predicate::PropertyKey(key) => {
+                let key = key.clone();
+                self.filter_property_bitmap(move |node| {
+                    matches!(&node.data, Value::Object(data_map) if data_map.contains_key(&key))
+                });
+            }
+            Predicate::PropertyValue(key, value) => {
+                let key = key.clone();
+                let value = value.clone();
+                self.filter_property_bitmap(move |node| {
+                    matches!(&node.data, Value::Object(data_map) if data_map.get(&key) == Some(&value))
+                });
+            }
+            Predicate::Path(operator, starting_path) => {
+                let search_results = self
+                    .basic_db
+                    .query_by_operator(operator, starting_path, "")
+                    .unwrap_or_default();
+                let paths: Vec<String> = search_results.into_iter().map(|item| item.path).collect();
+                self.filter_path_bitmap(&paths);
+            }
+        }
+    }
+
+    /// Splits `text` on non-alphanumeric boundaries and lowercases each piece,
+    /// so indexing and querying agree on what counts as a token.
+    fn tokenize(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_lowercase())
+            .collect()
+    }
+
+    /// Tokenizes the node's description and any top-level string fields in
+    /// its data, and folds the result into `text_index`/`doc_lengths`.
+    fn index_text_for(&mut self, path: &str, node: &TreeNode) {
+        let mut tokens = Vec::new();
+
+        if let Value::Object(data_map) = &node.data {
+            for value in data_map.values() {
+                if let Value::String(s) = value {
+                    tokens.extend(Self::tokenize(s));
+                }
+            }
+        }
+
+        self.doc_lengths.insert(path.to_string(), tokens.len());
+
+        for token in tokens {
+            let postings = self.text_index.entry(token).or_insert_with(HashMap::new);
+            *postings.entry(path.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Searches node descriptions and string fields for `query`, scoring
+    /// matches with BM25 (`k1 = 1.2`, `b = 0.75`) and restricting to the
+    /// current `filter_results`. Returns `(path, score)` pairs sorted by
+    /// descending score.
+    pub fn search_text(&self, query: &str) -> Vec<(String, f32)> {
+        const K1: f32 = 1.2;
+        const B: f32 = 0.75;
+
+        let query_tokens = Self::tokenize(query);
+        if query_tokens.is_empty() || self.doc_lengths.is_empty() {
+            return Vec::new();
+        }
+
+        let total_docs = self.doc_lengths.len() as f32;
+        let avg_doc_len = self.doc_lengths.values().sum::<usize>() as f32 / total_docs;
+
+        let mut scores: HashMap<String, f32> = HashMap::new();
+        for token in &query_tokens {
+            let Some(postings) = self.text_index.get(token) else {
+                continue;
+            };
+            let doc_freq = postings.len() as f32;
+            let idf = ((total_docs - doc_freq + 0.5) / (doc_freq + 0.5) + 1.0).ln();
+
+            for (path, &tf) in postings {
+                if !self.filter_results.contains_key(path) {
+                    continue;
+                }
+                let doc_len = *self.doc_lengths.get(path).unwrap_or(&0) as f32;
+                let tf = tf as f32;
+                let denom = tf + K1 * (1.0 - B + B * doc_len / avg_doc_len);
+                *scores.entry(path.clone()).or_insert(0.0) += idf * (tf * (K1 + 1.0)) / denom;
+            }
+        }
+
+        let mut ranked: Vec<(String, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+    }
+
+    /// Clears all filters and resets the query state
+    pub fn clear_filters(&mut self) {
+        self.filter_bitmap = Bitmap::full(self.id_to_path.len() as u32);
+        self.materialize_filter_results();
+    }
+
+    /// Searches for rows matching the specified knowledge base
+    pub fn search_kb(&mut self, knowledge_base: &str) -> &HashMap<String, TreeNode> {
+        self.filter_kb_bitmap(knowledge_base);
+        self.materialize_filter_results();
         &self.filter_results
     }
 
     /// Searches for rows matching the specified label
     pub fn search_label(&mut self, label: &str) -> &HashMap<String, TreeNode> {
-        let mut new_filter_results = HashMap::new();
-        
-        if let Some(label_keys) = self.labels.get(label) {
-            for key in label_keys {
-                if let Some(node) = self.filter_results.get(key) {
-                    new_filter_results.insert(key.clone(), node.clone());
+        self.filter_label_bitmap(label);
+        self.materialize_filter_results();
+        &self.filter_results
+    }
+
+    /// Searches for rows whose label is within `max_edits` edits of `label`,
+    /// to tolerate typos in label lookups.
+    pub fn search_label_fuzzy(&mut self, label: &str, max_edits: u32) -> &HashMap<String, TreeNode> {
+        let mut bitmap = Bitmap::default();
+        for (candidate, label_bitmap) in &self.labels {
+            if Self::bounded_levenshtein(label, candidate, max_edits).is_some() {
+                for id in label_bitmap.iter() {
+                    bitmap.set(id);
                 }
             }
         }
-        
-        self.filter_results = new_filter_results;
+        self.filter_bitmap = self.filter_bitmap.and(&bitmap);
+        self.materialize_filter_results();
         &self.filter_results
     }
 
     /// Searches for rows matching the specified name
     pub fn search_name(&mut self, name: &str) -> &HashMap<String, TreeNode> {
-        let mut new_filter_results = HashMap::new();
-        
-        if let Some(name_keys) = self.names.get(name) {
-            for key in name_keys {
-                if let Some(node) = self.filter_results.get(key) {
-                    new_filter_results.insert(key.clone(), node.clone());
+        self.filter_name_bitmap(name);
+        self.materialize_filter_results();
+        &self.filter_results
+    }
+
+    /// Searches for rows whose name is within `max_edits` edits of `name`,
+    /// to tolerate typos in name lookups.
+    pub fn search_name_fuzzy(&mut self, name: &str, max_edits: u32) -> &HashMap<String, TreeNode> {
+        let mut bitmap = Bitmap::default();
+        for (candidate, name_bitmap) in &self.names {
+            if Self::bounded_levenshtein(name, candidate, max_edits).is_some() {
+                for id in name_bitmap.iter() {
+                    bitmap.set(id);
                 }
             }
         }
-        
-        self.filter_results = new_filter_results;
+        self.filter_bitmap = self.filter_bitmap.and(&bitmap);
+        self.materialize_filter_results();
         &self.filter_results
     }
 
-    /// Searches for rows that contain the specified property key
-    pub fn search_property_key(&mut self, data_key: &str) -> &HashMap<String, TreeNode> {
-        let mut new_filter_results = HashMap::new();
-        
-        for (key, node) in &self.filter_results {
-            if let Value::Object(data_map) = &node.data {
-                if data_map.contains_key(data_key) {
-                    new_filter_results.insert(key.clone(), node.clone());
-                }
+    /// Computes the Levenshtein edit distance between `a` and `b`, aborting
+    /// early and returning `None` once the distance is provably greater than
+    /// `max_edits` (banded DP: a length-difference check up front, then a
+    /// per-row minimum check), so dissimilar candidates are cheap to reject.
+    fn bounded_levenshtein(a: &str, b: &str, max_edits: u32) -> Option<u32> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+
+        if (a.len() as i64 - b.len() as i64).unsigned_abs() as u32 > max_edits {
+            return None;
+        }
+
+        let mut prev: Vec<u32> = (0..=b.len() as u32).collect();
+        for i in 1..=a.len() {
+            let mut row = vec![0u32; b.len() + 1];
+            row[0] = i as u32;
+            let mut row_min = row[0];
+            for j in 1..=b.len() {
+                let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+                row_min = row_min.min(row[j]);
+            }
+            if row_min > max_edits {
+                return None;
             }
+            prev = row;
         }
-        
-        self.filter_results = new_filter_results;
+
+        let distance = prev[b.len()];
+        (distance <= max_edits).then_some(distance)
+    }
+
+    /// Searches for rows that contain the specified property key. There's no
+    /// index over data fields, so this only walks doc-ids already surviving
+    /// the current filter.
+    pub fn search_property_key(&mut self, data_key: &str) -> &HashMap<String, TreeNode> {
+        let data_key = data_key.to_string();
+        self.filter_property_bitmap(move |node| {
+            matches!(&node.data, Value::Object(data_map) if data_map.contains_key(&data_key))
+        });
+        self.materialize_filter_results();
         &self.filter_results
     }
 
     /// Searches for rows where the properties JSON field contains the specified key with the specified value
     pub fn search_property_value(&mut self, data_key: &str, data_value: &Value) -> &HashMap<String, TreeNode> {
-        let mut new_filter_results = HashMap::new();
-        
-        for (key, node) in &self.filter_results {
-            if let Value::Object(data_map) = &node.data {
-                if let Some(value) = data_map.get(data_key) {
-                    if value == data_value {
-                        new_filter_results.insert(key.clone(), node.clone());
-                    }
-                }
-            }
-        }
-        
-        self.filter_results = new_filter_results;
+        let data_key = data_key.to_string();
+        let data_value = data_value.clone();
+        self.filter_property_bitmap(move |node| {
+            matches!(&node.data, Value::Object(data_map) if data_map.get(&data_key) == Some(&data_value))
+        });
+        self.materialize_filter_results();
         &self.filter_results
     }
 
     /// Searches for a specific path and all its descendants
     pub fn search_starting_path(&mut self, starting_path: &str) -> Result<&HashMap<String, TreeNode>, SearchMemError> {
-        let mut new_filter_results = HashMap::new();
-        
-        // Add starting path if it exists in filter results
-        if let Some(node) = self.filter_results.get(starting_path) {
-            new_filter_results.insert(starting_path.to_string(), node.clone());
-        } else {
-            // If starting path doesn't exist, clear filter results
-            self.filter_results.clear();
+        let in_current_filter = self
+            .path_to_id
+            .get(starting_path)
+            .is_some_and(|&id| self.filter_bitmap.get(id));
+
+        if !in_current_filter {
+            // Starting path isn't present (or already filtered out): empty results.
+            self.filter_bitmap.clear();
+            self.materialize_filter_results();
             return Ok(&self.filter_results);
         }
-        
-        
-        // Get and add descendants
+
+        // Get descendants and include the starting path itself
         let descendants = self.basic_db.query_descendants(starting_path)
             .map_err(|e| SearchMemError::QueryFailed(e.to_string()))?;
 
-        for item in descendants {
-            if let Some(node) = self.filter_results.get(&item.path) {
-                new_filter_results.insert(item.path, node.clone());
-            }
-        }
-        
-        self.filter_results = new_filter_results;
+        let mut paths: Vec<String> = vec![starting_path.to_string()];
+        paths.extend(descendants.into_iter().map(|item| item.path));
+
+        self.filter_path_bitmap(&paths);
+        self.materialize_filter_results();
         Ok(&self.filter_results)
     }
 
-    
-
     /// Searches for rows matching the specified LTREE path expression using operators
     pub fn search_path<'a>(
         &mut self,
@@ -284,16 +628,11 @@ impl SearchMemDB {
         starting_path: &'a str,
     ) -> &HashMap<String, TreeNode> {
         // Use the parent class query method
-        let search_results = self.basic_db.query_by_operator(operator, starting_path,"");
-        
-        let mut new_filter_results = HashMap::new();
-        for item in search_results {
-            if let Some(node) = self.filter_results.get(&item.path) {
-                new_filter_results.insert(item.path.clone(), node.clone());
-            }
-        }
-        
-        self.filter_results = new_filter_results;
+        let search_results = self.basic_db.query_by_operator(operator, starting_path, "").unwrap_or_default();
+        let paths: Vec<String> = search_results.into_iter().map(|item| item.path).collect();
+
+        self.filter_path_bitmap(&paths);
+        self.materialize_filter_results();
         &self.filter_results
     }
 
@@ -333,19 +672,24 @@ impl SearchMemDB {
         self.filter_results.keys().cloned().collect()
     }
 
-    /// Returns all knowledge bases
-    pub fn get_kbs(&self) -> &HashMap<String, Vec<String>> {
-        &self.kbs
+    /// Resolves a bitmap of doc-ids to the paths they name.
+    fn resolve_paths(&self, bitmap: &Bitmap) -> Vec<String> {
+        bitmap.iter().map(|id| self.id_to_path[id as usize].clone()).collect()
+    }
+
+    /// Returns all knowledge bases, resolved from their doc-id bitmaps
+    pub fn get_kbs(&self) -> HashMap<String, Vec<String>> {
+        self.kbs.iter().map(|(kb, bitmap)| (kb.clone(), self.resolve_paths(bitmap))).collect()
     }
 
-    /// Returns all labels
-    pub fn get_labels(&self) -> &HashMap<String, Vec<String>> {
-        &self.labels
+    /// Returns all labels, resolved from their doc-id bitmaps
+    pub fn get_labels(&self) -> HashMap<String, Vec<String>> {
+        self.labels.iter().map(|(label, bitmap)| (label.clone(), self.resolve_paths(bitmap))).collect()
     }
 
-    /// Returns all names
-    pub fn get_names(&self) -> &HashMap<String, Vec<String>> {
-        &self.names
+    /// Returns all names, resolved from their doc-id bitmaps
+    pub fn get_names(&self) -> HashMap<String, Vec<String>> {
+        self.names.iter().map(|(name, bitmap)| (name.clone(), self.resolve_paths(bitmap))).collect()
     }
 
     /// Returns all decoded keys
@@ -373,6 +717,7 @@ impl SearchMemDB {
     pub fn add_data(&mut self, path: String, data: Value, created_at: Option<String>, updated_at: Option<String>) -> Result<(), SearchMemError> {
         self.basic_db.store(&path, data, created_at, updated_at)?;
         self.refresh_indices();
+        self.update_views_for_path(&path);
         Ok(())
     }
 
@@ -381,10 +726,126 @@ impl SearchMemDB {
         let result = self.basic_db.delete(path);
         if result {
             self.refresh_indices();
+            self.retract_views_for_path(path);
         }
         result
     }
 
+    /// Registers a secondary-index view: `map_fn` is run over every existing
+    /// node to backfill `name`'s postings, and from then on only the affected
+    /// path is re-indexed on `add_data`/`remove_data` rather than rebuilding
+    /// the whole view.
+    pub fn register_view(&mut self, name: &str, map_fn: ViewMapFn) {
+        self.view_fns.insert(name.to_string(), map_fn);
+        self.views.insert(name.to_string(), BTreeMap::new());
+        self.view_contributions.insert(name.to_string(), HashMap::new());
+
+        let all_paths = self.basic_db.get_all_paths();
+        for path in all_paths {
+            if let Ok(Some(node)) = self.basic_db.get_node(&path) {
+                self.apply_view_for_path(name, &path, Some(&node));
+            }
+        }
+    }
+
+    /// Re-indexes `path` under every registered view, using its current node.
+    fn update_views_for_path(&mut self, path: &str) {
+        let node = match self.basic_db.get_node(path) {
+            Ok(Some(node)) => node,
+            _ => return,
+        };
+        let view_names: Vec<String> = self.view_fns.keys().cloned().collect();
+        for name in view_names {
+            self.apply_view_for_path(&name, path, Some(&node));
+        }
+    }
+
+    /// Retracts `path`'s postings from every registered view after it's been removed.
+    fn retract_views_for_path(&mut self, path: &str) {
+        let view_names: Vec<String> = self.view_fns.keys().cloned().collect();
+        for name in view_names {
+            self.apply_view_for_path(&name, path, None);
+        }
+    }
+
+    /// Retracts `path`'s previous contribution to view `view_name`, then (if
+    /// `node` is `Some`) re-runs the view's map function and records the new
+    /// postings, patching `views` incrementally rather than rebuilding it.
+    fn apply_view_for_path(&mut self, view_name: &str, path: &str, node: Option<&TreeNode>) {
+        if let Some(contributions) = self.view_contributions.get_mut(view_name) {
+            if let Some(old_keys) = contributions.remove(path) {
+                if let Some(postings) = self.views.get_mut(view_name) {
+                    for key in old_keys {
+                        if let Some(Value::Array(entries)) = postings.get_mut(&key) {
+                            entries.retain(|entry| entry.get("path").and_then(Value::as_str) != Some(path));
+                            if entries.is_empty() {
+                                postings.remove(&key);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let Some(node) = node else { return };
+        let Some(map_fn) = self.view_fns.get(view_name) else { return };
+        let emitted = map_fn(path, node);
+        if emitted.is_empty() {
+            return;
+        }
+
+        let postings = self.views.entry(view_name.to_string()).or_insert_with(BTreeMap::new);
+        let mut new_keys = Vec::with_capacity(emitted.len());
+        for (key, payload) in emitted {
+            let entry = postings.entry(key.clone()).or_insert_with(|| Value::Array(Vec::new()));
+            if let Value::Array(entries) = entry {
+                entries.push(serde_json::json!({"path": path, "payload": payload}));
+            }
+            new_keys.push(key);
+        }
+
+        self.view_contributions
+            .entry(view_name.to_string())
+            .or_insert_with(HashMap::new)
+            .insert(path.to_string(), new_keys);
+    }
+
+    /// Filters to rows indexed under exactly `key` in the view `name`.
+    pub fn query_view(&mut self, name: &str, key: &str) -> &HashMap<String, TreeNode> {
+        let mut paths = Vec::new();
+        if let Some(Value::Array(entries)) = self.views.get(name).and_then(|postings| postings.get(key)) {
+            for entry in entries {
+                if let Some(path) = entry.get("path").and_then(Value::as_str) {
+                    paths.push(path.to_string());
+                }
+            }
+        }
+
+        self.filter_path_bitmap(&paths);
+        self.materialize_filter_results();
+        &self.filter_results
+    }
+
+    /// Filters to rows indexed under any key in `[lo, hi]` in the view `name`.
+    pub fn query_view_range(&mut self, name: &str, lo: &str, hi: &str) -> &HashMap<String, TreeNode> {
+        let mut paths = Vec::new();
+        if let Some(postings) = self.views.get(name) {
+            for (_, value) in postings.range(lo.to_string()..=hi.to_string()) {
+                if let Value::Array(entries) = value {
+                    for entry in entries {
+                        if let Some(path) = entry.get("path").and_then(Value::as_str) {
+                            paths.push(path.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        self.filter_path_bitmap(&paths);
+        self.materialize_filter_results();
+        &self.filter_results
+    }
+
     /// Gets the count of items in each category
     pub fn get_stats(&self) -> HashMap<String, usize> {
         let mut stats = HashMap::new();
@@ -400,62 +861,163 @@ impl SearchMemDB {
     pub fn chain_search(&mut self) -> SearchChain {
         SearchChain::new(self)
     }
+
+    /// Tallies, for each requested property key, how many times each value
+    /// occurs across the current `filter_results` — a multi-valued (array)
+    /// field counts every element, so a caller can render "N results" next
+    /// to each facet option without re-running the underlying query.
+    pub fn facet_distribution(&self, property_keys: &[&str]) -> HashMap<String, HashMap<String, usize>> {
+        let mut facets: HashMap<String, HashMap<String, usize>> = HashMap::new();
+
+        for node in self.filter_results.values() {
+            let Value::Object(data_map) = &node.data else { continue };
+            for &key in property_keys {
+                let Some(value) = data_map.get(key) else { continue };
+                let counts = facets.entry(key.to_string()).or_insert_with(HashMap::new);
+                Self::tally_facet_value(counts, value);
+            }
+        }
+
+        facets
+    }
+
+    /// Recursively tallies `value` into `counts`, keyed by its stringified
+    /// form; arrays are flattened so each element is tallied on its own.
+    fn tally_facet_value(counts: &mut HashMap<String, usize>, value: &Value) {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    Self::tally_facet_value(counts, item);
+                }
+            }
+            Value::String(s) => *counts.entry(s.clone()).or_insert(0) += 1,
+            other => *counts.entry(other.to_string()).or_insert(0) += 1,
+        }
+    }
+
+    /// Like `facet_distribution`, but over the decoded `kb`/`label`/`name`
+    /// path components rather than data fields, using `decoded_keys` so it
+    /// reflects exactly the paths currently in `filter_results`.
+    pub fn facet_distribution_components(&self) -> HashMap<String, HashMap<String, usize>> {
+        let mut facets: HashMap<String, HashMap<String, usize>> = HashMap::new();
+
+        for path in self.filter_results.keys() {
+            let Some(components) = self.decoded_keys.get(path) else { continue };
+            if components.len() < 3 {
+                continue;
+            }
+
+            let kb = &components[0];
+            let label = &components[components.len() - 2];
+            let name = &components[components.len() - 1];
+
+            *facets.entry("kb".to_string()).or_insert_with(HashMap::new).entry(kb.clone()).or_insert(0) += 1;
+            *facets.entry("label".to_string()).or_insert_with(HashMap::new).entry(label.clone()).or_insert(0) += 1;
+            *facets.entry("name".to_string()).or_insert_with(HashMap::new).entry(name.clone()).or_insert(0) += 1;
+        }
+
+        facets
+    }
 }
 
-/// Helper struct for chaining search operations
+/// Helper struct for chaining search operations. Each builder method defers
+/// its predicate instead of applying it immediately; `results()`/
+/// `result_keys()`/`explain()` reorder the accumulated predicates by
+/// estimated selectivity (most selective, index-backed filters first) before
+/// running them against `filter_bitmap`, so a cheap `kb("...")` cut always
+/// runs before an expensive unindexed property scan regardless of call order.
 pub struct SearchChain<'a> {
     search_db: &'a mut SearchMemDB,
+    predicates: Vec<Predicate>,
 }
 
 impl<'a> SearchChain<'a> {
     fn new(search_db: &'a mut SearchMemDB) -> Self {
-        Self { search_db }
+        Self { search_db, predicates: Vec::new() }
     }
 
     /// Chain a KB search
-    pub fn kb(self, knowledge_base: &str) -> Self {
-        self.search_db.search_kb(knowledge_base);
+    pub fn kb(mut self, knowledge_base: &str) -> Self {
+        self.predicates.push(Predicate::Kb(knowledge_base.to_string()));
         self
     }
 
     /// Chain a label search
-    pub fn label(self, label: &str) -> Self {
-        self.search_db.search_label(label);
+    pub fn label(mut self, label: &str) -> Self {
+        self.predicates.push(Predicate::Label(label.to_string()));
         self
     }
 
     /// Chain a name search
-    pub fn name(self, name: &str) -> Self {
-        self.search_db.search_name(name);
+    pub fn name(mut self, name: &str) -> Self {
+        self.predicates.push(Predicate::Name(name.to_string()));
         self
     }
 
     /// Chain a property key search
-    pub fn property_key(self, data_key: &str) -> Self {
-        self.search_db.search_property_key(data_key);
+    pub fn property_key(mut self, data_key: &str) -> Self {
+        self.predicates.push(Predicate::PropertyKey(data_key.to_string()));
         self
     }
 
     /// Chain a property value search
-    pub fn property_value(self, data_key: &str, data_value: &Value) -> Self {
-        self.search_db.search_property_value(data_key, data_value);
+    pub fn property_value(mut self, data_key: &str, data_value: &Value) -> Self {
+        self.predicates.push(Predicate::PropertyValue(data_key.to_string(), data_value.clone()));
         self
     }
 
     /// Chain a path search
-    pub fn path(self, operator: &str, starting_path: &str) -> Self {
-        self.search_db.search_path(operator, starting_path);
+    pub fn path(mut self, operator: &str, starting_path: &str) -> Self {
+        self.predicates.push(Predicate::Path(operator.to_string(), starting_path.to_string()));
         self
     }
 
-    /// Get the final results
+    /// Sorts the accumulated predicates ascending by estimated surviving-set
+    /// size and applies them in that order, returning the `SearchMemDB` back
+    /// along with each step's estimated/actual cardinality.
+    fn apply_optimized(self) -> (&'a mut SearchMemDB, Vec<PredicateExplain>) {
+        let SearchChain { search_db, mut predicates } = self;
+        // Ties go to index-backed predicates, so they still run before an
+        // unindexed scan whose fixed estimate happens to equal theirs.
+        predicates.sort_by_key(|p| {
+            let unindexed = matches!(p, Predicate::PropertyKey(_) | Predicate::PropertyValue(_, _) | Predicate::Path(_, _));
+            (search_db.estimate_predicate_cardinality(p), unindexed)
+        });
+
+        let mut steps = Vec::with_capacity(predicates.len());
+        for predicate in &predicates {
+            let estimated = search_db.estimate_predicate_cardinality(predicate);
+            search_db.apply_predicate(predicate);
+            steps.push(PredicateExplain {
+                predicate: predicate.describe(),
+                estimated_cardinality: estimated,
+                actual_cardinality: search_db.filter_bitmap.len(),
+            });
+        }
+
+        (search_db, steps)
+    }
+
+    /// Get the final results, after a cost-based reordering of predicates
     pub fn results(self) -> &'a HashMap<String, TreeNode> {
-        &self.search_db.filter_results
+        let (search_db, _) = self.apply_optimized();
+        search_db.materialize_filter_results();
+        &search_db.filter_results
     }
 
-    /// Get the final result keys
+    /// Get the final result keys, after a cost-based reordering of predicates
     pub fn result_keys(self) -> Vec<String> {
-        self.search_db.get_filter_result_keys()
+        let (search_db, _) = self.apply_optimized();
+        search_db.materialize_filter_results();
+        search_db.get_filter_result_keys()
+    }
+
+    /// Runs the chain and returns the chosen predicate order along with each
+    /// step's estimated and actual surviving-row count, for debugging.
+    pub fn explain(self) -> Vec<PredicateExplain> {
+        let (search_db, steps) = self.apply_optimized();
+        search_db.materialize_filter_results();
+        steps
     }
 }
 
@@ -578,6 +1140,31 @@ mod tests {
         assert!(results.contains_key("kb1.section1.item1"));
     }
 
+    #[test]
+    fn test_search_name_fuzzy() {
+        let mut db = create_test_db();
+
+        // "itme1" is one transposition away from "item1"
+        let results = db.search_name_fuzzy("itme1", 2);
+        assert!(results.contains_key("kb1.section1.item1"));
+
+        // Too far away to match within the edit budget
+        db.clear_filters();
+        let results = db.search_name_fuzzy("completely_different", 2);
+        assert_eq!(results.len(), 0);
+    }
+
+    #[test]
+    fn test_search_label_fuzzy() {
+        let mut db = create_test_db();
+
+        let results = db.search_label_fuzzy("secton1", 1);
+        for key in results.keys() {
+            assert!(key.contains(".section1."));
+        }
+        assert!(!results.is_empty());
+    }
+
     #[test]
     fn test_search_property_key() {
         let mut db = create_test_db();
@@ -625,11 +1212,50 @@ mod tests {
             .label("section1")
             .property_value("category", &json!("A"))
             .results();
-        
+
         assert_eq!(results.len(), 1);
         assert!(results.contains_key("kb1.section1.item1"));
     }
 
+    #[test]
+    fn test_chain_reorders_unindexed_predicate_after_indexed_one() {
+        let mut db = create_test_db();
+
+        // Written with the expensive unindexed scan first; the optimizer
+        // should still run the indexed `name` cut first.
+        let steps = db
+            .chain_search()
+            .property_value("category", &json!("A"))
+            .name("item1")
+            .explain();
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].predicate, "name(item1)");
+        assert_eq!(steps[1].predicate, "property_value(category, \"A\")");
+        assert_eq!(steps[1].actual_cardinality, 1);
+    }
+
+    #[test]
+    fn test_chain_explain_tracks_actual_cardinality() {
+        let mut db = create_test_db();
+
+        let steps = db.chain_search().kb("kb1").explain();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].actual_cardinality, 3);
+        assert_eq!(db.get_filter_results().len(), 3);
+    }
+
+    #[test]
+    fn test_get_kbs_reflects_current_data_after_mutation() {
+        let mut db = create_test_db();
+
+        assert_eq!(db.get_kbs()["kb1"].len(), 3);
+
+        assert!(db.remove_data("kb1.section1.item1"));
+        assert_eq!(db.get_kbs()["kb1"].len(), 2);
+        assert!(!db.get_kbs()["kb1"].contains(&"kb1.section1.item1".to_string()));
+    }
+
     #[test]
     fn test_clear_filters() {
         let mut db = create_test_db();
@@ -668,6 +1294,126 @@ mod tests {
         assert_eq!(results.len(), 0);
     }
 
+    #[test]
+    fn test_search_text_ranks_best_match_first() {
+        let db = create_test_db();
+
+        let results = db.search_text("first item");
+        assert!(!results.is_empty());
+        assert_eq!(results[0].0, "kb1.section1.item1");
+
+        // Scores should be sorted in descending order
+        for window in results.windows(2) {
+            assert!(window[0].1 >= window[1].1);
+        }
+    }
+
+    #[test]
+    fn test_search_text_respects_current_filters() {
+        let mut db = create_test_db();
+
+        db.search_kb("kb2");
+        let results = db.search_text("item");
+        assert!(results.iter().all(|(path, _)| path.starts_with("kb2.")));
+    }
+
+    #[test]
+    fn test_register_view_backfills_and_queries_by_key() {
+        let mut db = create_test_db();
+
+        db.register_view(
+            "by_category",
+            Box::new(|path, node| {
+                if let Value::Object(data_map) = &node.data {
+                    if let Some(Value::String(category)) = data_map.get("category") {
+                        return vec![(category.clone(), None)];
+                    }
+                }
+                vec![]
+            }),
+        );
+
+        let results = db.query_view("by_category", "A");
+        assert_eq!(results.len(), 2);
+        assert!(results.contains_key("kb1.section1.item1"));
+        assert!(results.contains_key("kb1.section2.item3"));
+    }
+
+    #[test]
+    fn test_register_view_maintained_incrementally_on_add_and_remove() {
+        let mut db = create_test_db();
+
+        db.register_view(
+            "by_category",
+            Box::new(|_path, node| {
+                if let Value::Object(data_map) = &node.data {
+                    if let Some(Value::String(category)) = data_map.get("category") {
+                        return vec![(category.clone(), None)];
+                    }
+                }
+                vec![]
+            }),
+        );
+
+        let _ = db.add_data(
+            "kb2.section2.item5".to_string(),
+            json!({"description": "Fifth item", "category": "A"}),
+            None,
+            None,
+        );
+        assert_eq!(db.query_view("by_category", "A").len(), 3);
+
+        db.clear_filters();
+        assert!(db.remove_data("kb2.section2.item5"));
+        assert_eq!(db.query_view("by_category", "A").len(), 2);
+    }
+
+    #[test]
+    fn test_query_view_narrows_filter_bitmap_for_chained_filters() {
+        // query_view must narrow `filter_bitmap`, not just `filter_results`,
+        // so a filter chained after it (search_kb/search_label/etc.) sees
+        // the view's restriction rather than falling back to the full or
+        // previous bitmap.
+        let mut db = create_test_db();
+
+        db.register_view(
+            "by_category",
+            Box::new(|_path, node| {
+                if let Value::Object(data_map) = &node.data {
+                    if let Some(Value::String(category)) = data_map.get("category") {
+                        return vec![(category.clone(), None)];
+                    }
+                }
+                vec![]
+            }),
+        );
+
+        // Category "A" only has members in kb1 (item1, item3); none in kb2.
+        db.query_view("by_category", "A");
+        let chained = db.search_kb("kb2");
+        assert!(chained.is_empty());
+    }
+
+    #[test]
+    fn test_query_view_range_uses_btreemap_ordering() {
+        let mut db = create_test_db();
+
+        db.register_view(
+            "by_category",
+            Box::new(|_path, node| {
+                if let Value::Object(data_map) = &node.data {
+                    if let Some(Value::String(category)) = data_map.get("category") {
+                        return vec![(category.clone(), None)];
+                    }
+                }
+                vec![]
+            }),
+        );
+
+        let results = db.query_view_range("by_category", "A", "B");
+        assert_eq!(results.len(), 3);
+    }
+
     #[test]
     fn test_get_stats() {
         let db = create_test_db();
@@ -679,6 +1425,42 @@ mod tests {
         assert!(stats["unique_labels"] >= 2);
         assert!(stats["unique_names"] >= 4);
     }
+
+    #[test]
+    fn test_facet_distribution_counts_property_values() {
+        let db = create_test_db();
+
+        let facets = db.facet_distribution(&["category"]);
+        let category_counts = &facets["category"];
+        assert_eq!(category_counts["A"], 2);
+        assert_eq!(category_counts["B"], 1);
+        assert_eq!(category_counts["C"], 1);
+    }
+
+    #[test]
+    fn test_facet_distribution_reflects_current_filter() {
+        let mut db = create_test_db();
+
+        db.search_kb("kb1");
+        let facets = db.facet_distribution(&["category"]);
+        let category_counts = &facets["category"];
+        assert_eq!(category_counts["A"], 2);
+        assert_eq!(category_counts["B"], 1);
+        assert!(!category_counts.contains_key("C"));
+    }
+
+    #[test]
+    fn test_facet_distribution_components_counts_path_pieces() {
+        let db = create_test_db();
+
+        let facets = db.facet_distribution_components();
+        assert_eq!(facets["kb"]["kb1"], 3);
+        assert_eq!(facets["kb"]["kb2"], 1);
+        assert_eq!(facets["label"]["section1"], 3);
+        assert_eq!(facets["label"]["section2"], 1);
+        assert_eq!(facets["name"]["item1"], 1);
+        assert_eq!(facets["name"]["item4"], 1);
+    }
 }
 
 
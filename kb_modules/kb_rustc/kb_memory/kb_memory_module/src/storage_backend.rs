@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use crate::basic_memory_module::{KbError, TreeNode};
+
+/// A pluggable persistence backend for a [`BasicConstructDB`](crate::basic_memory_module::BasicConstructDB).
+/// Modeled on a generic embedded key-value layer (open a named tree, point
+/// lookups, and an ordered scan), so the in-memory tree can sync to
+/// Postgres, a local embedded store, or any future backend without the
+/// query logic caring which.
+pub trait StorageBackend {
+    /// Opens (or creates) the named tree/table this backend persists into.
+    fn open_tree(&mut self, name: &str) -> Result<(), KbError>;
+
+    /// Inserts or overwrites the node stored at `path`.
+    fn insert(&mut self, path: &str, node: &TreeNode) -> Result<(), KbError>;
+
+    /// Looks up the node stored at `path`, if any.
+    fn get(&self, path: &str) -> Result<Option<TreeNode>, KbError>;
+
+    /// Removes the node stored at `path`, returning whether it existed.
+    fn remove(&mut self, path: &str) -> Result<bool, KbError>;
+
+    /// Returns every stored node, ordered by path.
+    fn iter(&self) -> Result<Vec<TreeNode>, KbError>;
+
+    /// Returns every stored node whose path starts with `prefix`, ordered by path.
+    fn range(&self, prefix: &str) -> Result<Vec<TreeNode>, KbError>;
+}
+
+/// In-memory backend, kept ordered by path so `range()` can be served without
+/// a full scan. `BasicConstructDB` does not create one on its own: with no
+/// backend attached via `set_backend`, it keeps nodes only in its own map.
+#[derive(Default)]
+pub struct InMemoryBackend {
+    tree_name: String,
+    data: BTreeMap<String, TreeNode>,
+}
+
+impl StorageBackend for InMemoryBackend {
+    fn open_tree(&mut self, name: &str) -> Result<(), KbError> {
+        self.tree_name = name.to_string();
+        Ok(())
+    }
+
+    fn insert(&mut self, path: &str, node: &TreeNode) -> Result<(), KbError> {
+        self.data.insert(path.to_string(), node.clone());
+        Ok(())
+    }
+
+    fn get(&self, path: &str) -> Result<Option<TreeNode>, KbError> {
+        Ok(self.data.get(path).cloned())
+    }
+
+    fn remove(&mut self, path: &str) -> Result<bool, KbError> {
+        Ok(self.data.remove(path).is_some())
+    }
+
+    fn iter(&self) -> Result<Vec<TreeNode>, KbError> {
+        Ok(self.data.values().cloned().collect())
+    }
+
+    fn range(&self, prefix: &str) -> Result<Vec<TreeNode>, KbError> {
+        Ok(self
+            .data
+            .range(prefix.to_string()..)
+            .take_while(|(path, _)| path.starts_with(prefix))
+            .map(|(_, node)| node.clone())
+            .collect())
+    }
+}
+
+/// Embedded single-file backend on top of `sled`, so a tree can be built up
+/// fully offline and synced to Postgres later (via
+/// `BasicConstructDB::sync_with_postgres`) instead of requiring a live
+/// database connection for every write.
+pub struct SledBackend {
+    db: sled::Db,
+    tree: sled::Tree,
+}
+
+impl SledBackend {
+    /// Opens (or creates) a sled database at `path` on disk.
+    pub fn open(path: &str) -> Result<Self, KbError> {
+        let db = sled::open(path).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        let tree = db
+            .open_tree("default")
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        Ok(Self { db, tree })
+    }
+
+    fn decode(bytes: &[u8]) -> Result<TreeNode, KbError> {
+        serde_json::from_slice(bytes).map_err(|e| KbError::DatabaseError(e.to_string()))
+    }
+}
+
+impl StorageBackend for SledBackend {
+    fn open_tree(&mut self, name: &str) -> Result<(), KbError> {
+        self.tree = self
+            .db
+            .open_tree(name)
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    fn insert(&mut self, path: &str, node: &TreeNode) -> Result<(), KbError> {
+        let bytes = serde_json::to_vec(node).map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        self.tree
+            .insert(path.as_bytes(), bytes)
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    fn get(&self, path: &str) -> Result<Option<TreeNode>, KbError> {
+        match self
+            .tree
+            .get(path.as_bytes())
+            .map_err(|e| KbError::DatabaseError(e.to_string()))?
+        {
+            Some(bytes) => Ok(Some(Self::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove(&mut self, path: &str) -> Result<bool, KbError> {
+        self.tree
+            .remove(path.as_bytes())
+            .map(|removed| removed.is_some())
+            .map_err(|e| KbError::DatabaseError(e.to_string()))
+    }
+
+    fn iter(&self) -> Result<Vec<TreeNode>, KbError> {
+        self.tree
+            .iter()
+            .values()
+            .map(|res| Self::decode(&res.map_err(|e| KbError::DatabaseError(e.to_string()))?))
+            .collect()
+    }
+
+    fn range(&self, prefix: &str) -> Result<Vec<TreeNode>, KbError> {
+        self.tree
+            .scan_prefix(prefix.as_bytes())
+            .values()
+            .map(|res| Self::decode(&res.map_err(|e| KbError::DatabaseError(e.to_string()))?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(path: &str) -> TreeNode {
+        TreeNode {
+            path: path.to_string(),
+            data: serde_json::json!({}),
+            created_at: None,
+            updated_at: None,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn test_in_memory_backend_insert_get_remove() {
+        let mut backend = InMemoryBackend::default();
+        backend.open_tree("test_table").unwrap();
+
+        backend.insert("root.a", &node("root.a")).unwrap();
+        assert_eq!(backend.get("root.a").unwrap().unwrap().path, "root.a");
+
+        assert!(backend.remove("root.a").unwrap());
+        assert!(backend.get("root.a").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_in_memory_backend_range_and_iter() {
+        let mut backend = InMemoryBackend::default();
+        backend.open_tree("test_table").unwrap();
+
+        backend.insert("root.a", &node("root.a")).unwrap();
+        backend.insert("root.b", &node("root.b")).unwrap();
+        backend.insert("other.c", &node("other.c")).unwrap();
+
+        assert_eq!(backend.iter().unwrap().len(), 3);
+
+        let under_root = backend.range("root.").unwrap();
+        assert_eq!(under_root.len(), 2);
+        assert!(under_root.iter().all(|n| n.path.starts_with("root.")));
+    }
+}